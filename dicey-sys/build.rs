@@ -15,7 +15,7 @@
  */
 
 use std::{
-    env, fmt,
+    env, fmt, fs,
     ops::Deref,
     path::{Path, PathBuf},
 };
@@ -119,8 +119,16 @@ fn discover_explicit() -> Option<IncDir> {
 }
 
 fn discover_dicey() -> Option<IncDir> {
-    pkg_config::Config::new()
-        .atleast_version("0.3.9")
+    let mut config = pkg_config::Config::new();
+
+    if cfg!(feature = "pinned") {
+        // only accept a system libdicey if it's exactly the one the vendored headers describe
+        config.exactly_version(&vendored_version());
+    } else {
+        config.atleast_version("0.3.9");
+    }
+
+    config
         .statik(cfg!(feature = "static"))
         .probe("dicey")
         .ok()
@@ -167,11 +175,65 @@ fn is_windows() -> bool {
     env::var("CARGO_CFG_TARGET_OS").unwrap() == "windows"
 }
 
+fn vendored_version() -> String {
+    const VERSION_H: &str = "libdicey/include/dicey/core/version.h";
+
+    println!("cargo:rerun-if-changed={VERSION_H}");
+
+    let header = fs::read_to_string(VERSION_H).expect("failed to read the vendored version.h");
+
+    let component = |name: &str| {
+        header
+            .lines()
+            .find_map(|line| {
+                line.strip_prefix("#define ")?
+                    .strip_prefix(name)?
+                    .trim()
+                    .parse::<u32>()
+                    .ok()
+            })
+            .unwrap_or_else(|| panic!("{name} not found in {VERSION_H}"))
+    };
+
+    format!(
+        "{}.{}.{}",
+        component("DICEY_LIB_VERSION_MAJOR"),
+        component("DICEY_LIB_VERSION_MINOR"),
+        component("DICEY_LIB_VERSION_PATCH")
+    )
+}
+
+// packagers must link against the system's libraries only, so building the vendored ones is an error instead of a
+// fallback
+fn require_system_deps() -> Option<IncDir> {
+    let Some(incdir) = discover_explicit().or_else(discover_dicey) else {
+        panic!(
+            "system-deps-only is enabled, but no system libdicey was found; install it or point DICEY_PATH to it"
+        );
+    };
+
+    assert!(
+        discover_uv(),
+        "system-deps-only is enabled, but pkg-config could not find libuv"
+    );
+
+    assert!(
+        discover_xml2(),
+        "system-deps-only is enabled, but pkg-config could not find libxml-2.0"
+    );
+
+    Some(incdir)
+}
+
 fn main() {
-    let incdir = discover_explicit()
-        .or_else(discover_dicey)
-        .or_else(build_dicey)
-        .unwrap();
+    let incdir = if cfg!(feature = "system-deps-only") {
+        require_system_deps()
+    } else {
+        discover_explicit()
+            .or_else(discover_dicey)
+            .or_else(build_dicey)
+    }
+    .unwrap();
 
     let hpath = incdir.join("dicey").join("dicey.h");
     let bindings = bindgen::Builder::default()
@@ -21,6 +21,20 @@
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
+// limits of the wire format, which libdicey only spells out in its private headers
+
+// items in an array or tuple
+pub const DICEY_MAX_LIST_LEN: usize = u16::MAX as usize;
+
+// length of a byte buffer
+pub const DICEY_MAX_BYTES_LEN: usize = u32::MAX as usize;
+
+// length of a path, trait name or element name, not counting the terminating NUL
+pub const DICEY_MAX_PATH_LEN: usize = u32::MAX as usize;
+
+// length of a whole packet, i.e. a 12 bytes message header followed by at most u32::MAX bytes of payload
+pub const DICEY_MAX_PACKET_LEN: u64 = 12 + u32::MAX as u64;
+
 #[cfg(test)]
 mod tests {
     use std::ffi::CString;
@@ -0,0 +1,72 @@
+/*
+ * Copyright (c) 2014-2024 Zuru Tech HK Limited, All rights reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// compares reading a received array of floats straight off the message against decoding it into views first. There's
+// no benchmarking harness among the dependencies, so this is a plain binary: run it with `cargo bench`
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use dicey::{Message, MessageBuilder};
+
+const ROUNDS: u32 = 200;
+
+fn message_with(len: usize) -> Message {
+    let floats: Vec<f64> = (0..len).map(|i| i as f64 / 3.0).collect();
+
+    MessageBuilder::set()
+        .unwrap()
+        .path("/bench")
+        .unwrap()
+        .selector(("dicey.Bench", "Floats"))
+        .unwrap()
+        .value(floats)
+        .unwrap()
+        .build()
+        .unwrap()
+}
+
+fn time(f: impl Fn() -> usize) -> Duration {
+    let start = Instant::now();
+
+    for _ in 0..ROUNDS {
+        black_box(f());
+    }
+
+    start.elapsed() / ROUNDS
+}
+
+fn main() {
+    for len in [16, 1000, 65535] {
+        let msg = message_with(len);
+
+        let primitive = time(|| msg.value_primitive_array::<f64>().unwrap().len());
+        let view = time(|| {
+            msg.value()
+                .unwrap()
+                .extract_primitive_array::<f64>()
+                .unwrap()
+                .len()
+        });
+        let generic = time(|| msg.value().unwrap().extract::<Vec<f64>>().unwrap().len());
+
+        println!(
+            "{len:>6} items: {primitive:>10.2?} primitive, {view:>10.2?} view, {generic:>10.2?} generic"
+        );
+    }
+}
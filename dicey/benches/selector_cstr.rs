@@ -0,0 +1,85 @@
+/*
+ * Copyright (c) 2014-2024 Zuru Tech HK Limited, All rights reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// builds an exec for every element of a made-up introspected object, as a code-generated client calling each operation
+// would, once converting the names for every request and once reusing names converted up front. There's no benchmarking
+// harness among the dependencies, so this is a plain binary: run it with `cargo bench`
+
+use std::{
+    ffi::CString,
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use dicey::{Message, MessageBuilder};
+
+const ROUNDS: u32 = 2000;
+
+fn exec(builder: MessageBuilder<dicey::ExecOp>) -> Message {
+    builder.value(42u32).unwrap().build().unwrap()
+}
+
+fn time(f: impl Fn()) -> Duration {
+    let start = Instant::now();
+
+    for _ in 0..ROUNDS {
+        f();
+    }
+
+    start.elapsed() / ROUNDS
+}
+
+fn main() {
+    let elements: Vec<(String, String)> = (0..64)
+        .map(|i| {
+            (
+                format!("dicey.bench.Trait{}", i / 8),
+                format!("Operation{i}"),
+            )
+        })
+        .collect();
+
+    let cnames: Vec<(CString, CString)> = elements
+        .iter()
+        .map(|(tname, elem)| {
+            (
+                CString::new(tname.as_str()).unwrap(),
+                CString::new(elem.as_str()).unwrap(),
+            )
+        })
+        .collect();
+
+    let builder = || MessageBuilder::exec().unwrap().path("/bench").unwrap();
+
+    let selector = time(|| {
+        for (tname, elem) in &elements {
+            black_box(exec(
+                builder().selector((tname.as_str(), elem.as_str())).unwrap(),
+            ));
+        }
+    });
+
+    let selector_cstr = time(|| {
+        for (tname, elem) in &cnames {
+            black_box(exec(builder().selector_cstr(tname, elem).unwrap()));
+        }
+    });
+
+    println!(
+        "{} requests: {selector:>10.2?} selector, {selector_cstr:>10.2?} selector_cstr",
+        elements.len()
+    );
+}
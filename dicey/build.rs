@@ -63,6 +63,46 @@ use dicey_sys::{{dicey_error, dicey_error_msg}};
     pub const fn code(self) -> i32 {{
         self as i32
     }}
+
+    pub fn message(self) -> &'static str {{
+        // the messages are static strings owned by the C library
+        unsafe {{
+            let cmsg = dicey_error_msg(self.code());
+            CStr::from_ptr(cmsg).to_str().unwrap()
+        }}
+    }}
+}}
+"#
+    )?;
+
+    // all known errors, in the same order as the table in the C library
+    writeln!(write, "impl Error {{\n    pub(crate) const ALL: &[Error] = &[")?;
+
+    for def in defs {
+        let name = unsafe { CStr::from_ptr(def.name) }.to_str().unwrap(); // we assume all strings are ASCII
+        writeln!(write, "        Error::{name},")?;
+    }
+
+    writeln!(
+        write,
+        r#"    ];
+
+    /// Lists every error known to libdicey as `(variant, name, message)`, in the order of libdicey's own table.
+    pub const fn all() -> &'static [(Error, &'static str, &'static str)] {{
+        &["#
+    )?;
+
+    for def in defs {
+        let name = unsafe { CStr::from_ptr(def.name) }.to_str().unwrap(); // we assume all strings are ASCII
+        let message = unsafe { CStr::from_ptr(def.message) }.to_str().unwrap();
+
+        writeln!(write, "            (Error::{name}, {name:?}, {message:?}),")?;
+    }
+
+    writeln!(
+        write,
+        r#"        ]
+    }}
 }}
 
 impl error::Error for Error {{}}
@@ -73,12 +113,7 @@ impl error::Error for Error {{}}
         write,
         r#"impl Display for Error {{
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {{
-        let msg = unsafe {{
-            let cmsg = dicey_error_msg(self.code());
-            CStr::from_ptr(cmsg).to_str().unwrap()
-        }};
-
-        write!(f, "{{msg}}")
+        f.write_str(self.message())
     }}
 }}
 
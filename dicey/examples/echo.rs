@@ -19,8 +19,9 @@ use std::error;
 use clap::Parser;
 
 use dicey::{
-    Message,
+    Message, Path,
     blocking::{Client, ClientArgs},
+    path,
 };
 use uuid::Uuid;
 
@@ -29,29 +30,24 @@ struct Opts {
     socket: String,
 }
 
-const ECHO_PATH: &str = "/dicey/test/echo";
+const ECHO_PATH: &Path = path!("/dicey/test/echo");
 const ECHO_TRAIT: &str = "dicey.test.Echo";
 const ECHO_ECHO_ELEMENT: &str = "Echo";
 
 fn main() -> Result<(), Box<dyn error::Error>> {
     let opts = Opts::parse();
 
-    let cln = Client::connect(ClientArgs {
-        pipe: &opts.socket,
-        on_event: Some(|m: Message| {
+    let cln = Client::connect(
+        ClientArgs::new(&opts.socket).on_event(|_: &(), m: Message| {
             println!("received event: {m:?}");
         }),
-    })?;
+    )?;
 
     let uuid = Uuid::new_v4();
 
     println!("uuid (send) = {uuid}");
 
-    let response: Uuid = cln
-        .exec(ECHO_PATH, (ECHO_TRAIT, ECHO_ECHO_ELEMENT), uuid)?
-        .value()
-        .unwrap()
-        .extract()?;
+    let response: Uuid = cln.call(ECHO_PATH, (ECHO_TRAIT, ECHO_ECHO_ELEMENT), uuid)?;
 
     println!("uuid (recv) = {response}");
 
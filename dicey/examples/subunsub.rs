@@ -20,7 +20,7 @@ use clap::Parser;
 
 use tokio::time::sleep;
 
-use dicey::Client;
+use dicey::{Client, Path, path};
 
 #[derive(Parser)]
 struct Opts {
@@ -28,7 +28,7 @@ struct Opts {
     seconds: u64,
 }
 
-const TEST_TIMER_PATH: &str = "/dicey/test/timer";
+const TEST_TIMER_PATH: &Path = path!("/dicey/test/timer");
 const TEST_TIMER_TRAIT: &str = "dicey.test.Timer";
 const TEST_TIMER_START_ELEMENT: &str = "Start";
 const TEST_TIMER_TIMERFIRED_ELEMENT: &str = "TimerFired";
@@ -53,15 +53,12 @@ async fn main() -> Result<(), Box<dyn error::Error>> {
     .await?;
 
     let _: () = cln
-        .exec(
+        .call(
             TEST_TIMER_PATH,
             (TEST_TIMER_TRAIT, TEST_TIMER_START_ELEMENT),
             i32::try_from(opts.seconds)?,
         )
-        .await?
-        .value()
-        .unwrap()
-        .extract()?;
+        .await?;
 
     sleep(Duration::from_secs(opts.seconds + 1)).await;
 
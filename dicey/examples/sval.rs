@@ -19,8 +19,9 @@ use std::{error, time::Instant};
 use clap::Parser;
 
 use dicey::{
-    Message,
+    Message, Path,
     blocking::{Client, ClientArgs},
+    path,
 };
 
 #[derive(Parser)]
@@ -32,7 +33,7 @@ struct Opts {
     time: bool,
 }
 
-const SVAL_PATH: &str = "/sval";
+const SVAL_PATH: &Path = path!("/sval");
 const SVAL_TRAIT: &str = "sval.Sval";
 const SVAL_PROP: &str = "Value";
 
@@ -57,12 +58,11 @@ fn estimate(reqtime_us: f64) -> (f64, Option<char>) {
 fn main() -> Result<(), Box<dyn error::Error>> {
     let opts = Opts::parse();
 
-    let cln = Client::connect(ClientArgs {
-        pipe: &opts.socket,
-        on_event: Some(|m: Message| {
+    let cln = Client::connect(
+        ClientArgs::new(&opts.socket).on_event(|_: &(), m: Message| {
             println!("received event: {m:?}");
         }),
-    })?;
+    )?;
 
     let start = Instant::now();
     let elapsed = if let Some(value) = opts.value {
@@ -0,0 +1,75 @@
+/*
+ * Copyright (c) 2014-2024 Zuru Tech HK Limited, All rights reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{ptr, slice, sync::OnceLock};
+
+use dicey_sys::dicey_error_infos;
+
+use super::errors::Error;
+
+/// Checks that the libdicey loaded at runtime matches the one this crate was generated against.
+///
+/// The check compares the error table exported by the library with the one baked into [`Error`] at build time, and
+/// fails with [`Error::InvalidData`] if they differ. The result is computed once and cached.
+///
+/// libdicey only exposes its version as header macros, with no symbol to query it at runtime, so a library that
+/// changed in some other way while keeping the same error table passes the check.
+pub fn check_abi() -> Result<(), Error> {
+    static RESULT: OnceLock<Result<(), Error>> = OnceLock::new();
+
+    *RESULT.get_or_init(|| {
+        let defs = unsafe {
+            let mut defs_ptr = ptr::null();
+            let mut count = 0usize;
+
+            dicey_error_infos(&mut defs_ptr, &mut count);
+
+            if defs_ptr.is_null() {
+                return Err(Error::InvalidData);
+            }
+
+            slice::from_raw_parts(defs_ptr, count)
+        };
+
+        let matches = defs.len() == Error::ALL.len()
+            && defs
+                .iter()
+                .zip(Error::ALL)
+                .all(|(def, err)| def.errnum == err.code());
+
+        if matches {
+            Ok(())
+        } else {
+            Err(Error::InvalidData)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_table() {
+        assert_eq!(check_abi(), Ok(()));
+        assert_eq!(Error::all().len(), Error::ALL.len());
+
+        for &(err, name, message) in Error::all() {
+            assert_eq!(format!("{err:?}"), name);
+            assert_eq!(err.message(), message);
+        }
+    }
+}
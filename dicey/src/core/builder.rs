@@ -17,7 +17,9 @@
 use std::{
     borrow::Cow,
     ffi::{CStr, CString, c_char},
-    mem,
+    marker::PhantomData,
+    mem::{self, ManuallyDrop},
+    ptr,
 };
 
 use uuid::Uuid;
@@ -26,43 +28,175 @@ use dicey_sys::{
     dicey_arg, dicey_arg__bindgen_ty_1, dicey_bytes_arg, dicey_error_arg, dicey_message_builder,
     dicey_message_builder_begin, dicey_message_builder_build, dicey_message_builder_discard,
     dicey_message_builder_init, dicey_message_builder_set_path, dicey_message_builder_set_selector,
-    dicey_message_builder_value_end, dicey_message_builder_value_start, dicey_selector, dicey_uuid,
-    dicey_value_builder, dicey_value_builder_array_end, dicey_value_builder_array_start,
-    dicey_value_builder_next, dicey_value_builder_pair_end, dicey_value_builder_pair_start,
-    dicey_value_builder_set, dicey_value_builder_tuple_end, dicey_value_builder_tuple_start,
+    dicey_message_builder_set_seq, dicey_message_builder_value_end,
+    dicey_message_builder_value_start, dicey_selector, dicey_uuid, dicey_value_builder,
+    dicey_value_builder_array_end, dicey_value_builder_array_start, dicey_value_builder_next,
+    dicey_value_builder_pair_end, dicey_value_builder_pair_start, dicey_value_builder_set,
+    dicey_value_builder_tuple_end, dicey_value_builder_tuple_start,
 };
 
 use super::{
-    Error, Message, Op, RawPacket, Selector,
+    Error, MAX_BYTES_LEN, MAX_LIST_LEN, Message, Op, RawPacket, Selector,
     macros::ccall,
-    value::{Byte, ErrorMessage, Path, Type, bytes_to_cpath},
+    owned::OwnedValue,
+    signature::Signature,
+    value::{
+        Byte, ErrorMessage, Path, SelectorBuf, Tuple2, Type, ValueView, bytes_to_cpath,
+        bytes_to_cstring,
+    },
 };
 
-pub struct MessageBuilder {
+// markers for the operation a builder is for. Builders for an operation known at compile time only expose the methods
+// that make sense for it, while AnyOp builders check at runtime
+pub struct AnyOp;
+pub struct EventOp;
+pub struct ExecOp;
+
+/// Marks builders for Get requests, which carry no value.
+///
+/// ```compile_fail
+/// dicey::MessageBuilder::get()?.value(42u32);
+/// # Ok::<(), dicey::Error>(())
+/// ```
+pub struct GetOp;
+pub struct ResponseOp;
+
+/// Marks builders for Set requests that have no value yet, and so can't be built.
+///
+/// ```compile_fail
+/// dicey::MessageBuilder::set()?
+///     .path("/foo")?
+///     .selector(("a.Trait", "Prop"))?
+///     .build()?;
+/// # Ok::<(), dicey::Error>(())
+/// ```
+pub struct SetOp;
+
+// marks builders for Set requests once they have been given a value
+pub struct ValuedSetOp;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+// implemented by the markers of builders that can be given a value. Valued is the marker of the builder once it has one
+pub trait ValueOp: sealed::Sealed {
+    type Valued;
+}
+
+// implemented by the markers of builders that can be built
+pub trait BuildOp: sealed::Sealed {}
+
+macro_rules! impl_markers {
+    ($($marker:ident),+) => {
+        $(
+            impl sealed::Sealed for $marker {}
+        )+
+    };
+}
+
+macro_rules! impl_value_op {
+    ($($marker:ident),+) => {
+        $(
+            impl ValueOp for $marker {
+                type Valued = $marker;
+            }
+        )+
+    };
+}
+
+macro_rules! impl_build_op {
+    ($($marker:ident),+) => {
+        $(
+            impl BuildOp for $marker {}
+        )+
+    };
+}
+
+impl_markers!(
+    AnyOp,
+    EventOp,
+    ExecOp,
+    GetOp,
+    ResponseOp,
+    SetOp,
+    ValuedSetOp
+);
+
+impl_value_op!(AnyOp, EventOp, ExecOp, ResponseOp, ValuedSetOp);
+
+impl ValueOp for SetOp {
+    type Valued = ValuedSetOp;
+}
+
+impl_build_op!(AnyOp, EventOp, ExecOp, GetOp, ResponseOp, ValuedSetOp);
+
+pub struct MessageBuilder<K = AnyOp> {
     cbuilder: dicey_message_builder,
 
+    op: Op,
     seq: Option<u32>,
     path: Option<CString>,
     tname: Option<CString>,
     elem: Option<CString>,
 
-    cache: Vec<Vec<u8>>,
+    cache: PayloadCache,
+
+    kind: PhantomData<K>,
+}
+
+// the strings and bytes libdicey points to until the message is built
+#[derive(Default)]
+struct PayloadCache {
+    payloads: Vec<Vec<u8>>,
+    len: usize,
+    limit: Option<usize>,
+}
+
+impl PayloadCache {
+    fn push(&mut self, payload: Vec<u8>) -> Result<&[u8], Error> {
+        let len = self.len + payload.len();
+
+        if self.limit.is_some_and(|limit| len > limit) {
+            return Err(Error::Overflow);
+        }
+
+        self.len = len;
+        self.payloads.push(payload);
+
+        Ok(self.payloads.last().unwrap())
+    }
 }
 
 impl MessageBuilder {
-    pub fn event() -> Result<Self, Error> {
-        Self::new(Op::Event)
+    pub fn event() -> Result<MessageBuilder<EventOp>, Error> {
+        MessageBuilder::begin(Op::Event)
     }
 
-    pub fn exec() -> Result<Self, Error> {
-        Self::new(Op::Exec)
+    pub fn exec() -> Result<MessageBuilder<ExecOp>, Error> {
+        MessageBuilder::begin(Op::Exec)
     }
 
-    pub fn get() -> Result<Self, Error> {
-        Self::new(Op::Get)
+    pub fn get() -> Result<MessageBuilder<GetOp>, Error> {
+        MessageBuilder::begin(Op::Get)
     }
 
+    // for ops only known at runtime; setting a value on a Get builder fails
     pub fn new(kind: Op) -> Result<Self, Error> {
+        Self::begin(kind)
+    }
+
+    pub fn response() -> Result<MessageBuilder<ResponseOp>, Error> {
+        MessageBuilder::begin(Op::Response)
+    }
+
+    pub fn set() -> Result<MessageBuilder<SetOp>, Error> {
+        MessageBuilder::begin(Op::Set)
+    }
+}
+
+impl<K> MessageBuilder<K> {
+    fn begin(kind: Op) -> Result<Self, Error> {
         let cbuilder = unsafe {
             let mut cbuilder = mem::zeroed();
 
@@ -75,31 +209,66 @@ impl MessageBuilder {
         Ok(Self {
             cbuilder,
 
+            op: kind,
             seq: None,
             path: None,
             tname: None,
             elem: None,
 
-            cache: Vec::new(),
+            cache: PayloadCache::default(),
+
+            kind: PhantomData,
         })
     }
 
-    pub fn response() -> Result<Self, Error> {
-        Self::new(Op::Response)
+    // the size of the strings and bytes copied into the builder so far, which are held until the message is built
+    pub const fn cached_bytes(&self) -> usize {
+        self.cache.len
     }
 
-    pub fn set() -> Result<Self, Error> {
-        Self::new(Op::Set)
+    pub fn current_selector(&self) -> Option<Selector<'_>> {
+        match (&self.tname, &self.elem) {
+            (Some(tname), Some(elem)) => Some(Selector {
+                trait_name: tname.to_bytes(),
+                elem: elem.to_bytes(),
+            }),
+            _ => None,
+        }
     }
 
-    pub fn build(mut self) -> Result<Message, Error> {
-        let mut cmsg = unsafe { mem::zeroed() };
+    pub const fn current_seq(&self) -> Option<u32> {
+        self.seq
+    }
+
+    // forgets the op the builder was statically created for
+    pub fn into_dynamic(self) -> MessageBuilder {
+        self.retag()
+    }
 
+    // moves the builder to another marker, without touching what was built so far
+    fn retag<L>(self) -> MessageBuilder<L> {
+        let this = ManuallyDrop::new(self);
+
+        // this is never dropped, so its fields can be moved out
         unsafe {
-            ccall!(message_builder_build, &mut self.cbuilder, &mut cmsg)?;
+            MessageBuilder {
+                cbuilder: ptr::read(&this.cbuilder),
+
+                op: this.op,
+                seq: this.seq,
+                path: ptr::read(&this.path),
+                tname: ptr::read(&this.tname),
+                elem: ptr::read(&this.elem),
+
+                cache: ptr::read(&this.cache),
+
+                kind: PhantomData,
+            }
         }
+    }
 
-        RawPacket::from(cmsg).try_into()
+    pub const fn op(&self) -> Op {
+        self.op
     }
 
     pub fn path(mut self, path: impl Into<Vec<u8>>) -> Result<Self, Error> {
@@ -114,26 +283,38 @@ impl MessageBuilder {
         Ok(self)
     }
 
-    pub fn selector<'a>(mut self, sel: impl Into<Selector<'a>>) -> Result<Self, Error> {
+    pub fn path_str(&self) -> Option<&str> {
+        self.path.as_ref().and_then(|path| path.to_str().ok())
+    }
+
+    pub fn selector<'a>(self, sel: impl Into<Selector<'a>>) -> Result<Self, Error> {
         let sel = sel.into();
 
-        let trait_name = strip_null(sel.trait_name);
-        let elem = strip_null(sel.elem);
+        self.set_selector(
+            bytes_to_cstring(sel.trait_name)?,
+            bytes_to_cstring(sel.elem)?,
+        )
+    }
 
-        let tname = Some(CString::new(trait_name).map_err(|_| Error::InvalidData)?);
-        let elem = Some(CString::new(elem).map_err(|_| Error::InvalidData)?);
+    // like selector, but with names already converted to C strings, which only need to be copied instead of checked and
+    // converted. Useful when sending many requests to the same elements
+    pub fn selector_cstr(self, trait_name: &CStr, elem: &CStr) -> Result<Self, Error> {
+        self.set_selector(trait_name.to_owned(), elem.to_owned())
+    }
 
+    // libdicey only keeps pointers to the selector until the message is built, so the builder holds on to the names
+    fn set_selector(mut self, tname: CString, elem: CString) -> Result<Self, Error> {
         let csel = dicey_selector {
-            trait_: tname.as_ref().unwrap().as_ptr() as *const c_char,
-            elem: elem.as_ref().unwrap().as_ptr() as *const c_char,
+            trait_: tname.as_ptr(),
+            elem: elem.as_ptr(),
         };
 
         unsafe {
             ccall!(message_builder_set_selector, &mut self.cbuilder, csel)?;
         }
 
-        self.tname = tname;
-        self.elem = elem;
+        self.tname = Some(tname);
+        self.elem = Some(elem);
 
         Ok(self)
     }
@@ -144,14 +325,63 @@ impl MessageBuilder {
         self
     }
 
-    pub fn value(self, value: impl ToDicey) -> Result<Self, Error> {
+    /// Caps the bytes [`cached_bytes`](Self::cached_bytes) may reach; setting a value that would go past it fails
+    /// with `Error::Overflow`.
+    ///
+    /// Useful to bound the memory taken by messages built from sizes that come from untrusted input.
+    pub const fn with_cache_limit(mut self, bytes: usize) -> Self {
+        self.cache.limit = Some(bytes);
+
+        self
+    }
+}
+
+impl<K: BuildOp> MessageBuilder<K> {
+    pub fn build(mut self) -> Result<Message, Error> {
+        let mut cmsg = unsafe { mem::zeroed() };
+
+        unsafe {
+            if let Some(seq) = self.seq {
+                ccall!(message_builder_set_seq, &mut self.cbuilder, seq)?;
+            }
+
+            ccall!(message_builder_build, &mut self.cbuilder, &mut cmsg)?;
+        }
+
+        RawPacket::from(cmsg).try_into()
+    }
+}
+
+impl<K: ValueOp> MessageBuilder<K> {
+    pub fn value(self, value: impl ToDicey) -> Result<MessageBuilder<K::Valued>, Error> {
         self.value_with(|builder| builder.set(value))
     }
 
-    pub fn value_with<F>(mut self, op: F) -> Result<Self, Error>
+    // with a key, the value is sent as a `{#v}` pair after it, which is how the clients' RequestBuilders tag requests
+    // with their idempotency key
+    pub(crate) fn value_with_key<F>(
+        self,
+        key: Option<Uuid>,
+        op: F,
+    ) -> Result<MessageBuilder<K::Valued>, Error>
+    where
+        F: FnOnce(&mut ValueBuilder) -> Result<(), Error>,
+    {
+        match key {
+            Some(key) => self.value_with(|builder| builder.pair_with(|first| first.set(key), op)),
+            None => self.value_with(op),
+        }
+    }
+
+    pub fn value_with<F>(mut self, op: F) -> Result<MessageBuilder<K::Valued>, Error>
     where
         F: FnOnce(&mut ValueBuilder) -> Result<(), Error>,
     {
+        // only reachable with builders created by new
+        if self.op == Op::Get {
+            return Err(Error::InvalidData);
+        }
+
         unsafe {
             // do not move the cbuilder! the message builder expects the value builder to be in the same memory location
             // for the duration of the value building process.
@@ -174,12 +404,36 @@ impl MessageBuilder {
                 &mut valbuilder.cbuilder
             );
 
-            res.and_then(|_| end_res.map(|_| self))
+            res.and_then(|_| end_res.map(|_| self.retag()))
         }
     }
+
+    // builds the value through a builder that checks each item against `sig` while it's set. Given an operation's
+    // signature, responses are checked against its output and everything else against its input
+    pub fn value_for_signature<F>(
+        self,
+        sig: &str,
+        op: F,
+    ) -> Result<MessageBuilder<K::Valued>, Error>
+    where
+        F: FnOnce(&mut TypedValueBuilder) -> Result<(), Error>,
+    {
+        let sig = match Signature::parse(sig)? {
+            Signature::Operation { output, .. } if self.op == Op::Response => *output,
+            Signature::Operation { input, .. } => *input,
+            sig => sig,
+        };
+
+        self.value_with(|builder| {
+            op(&mut TypedValueBuilder {
+                builder,
+                expected: &sig,
+            })
+        })
+    }
 }
 
-impl Drop for MessageBuilder {
+impl<K> Drop for MessageBuilder<K> {
     fn drop(&mut self) {
         unsafe {
             dicey_message_builder_discard(&mut self.cbuilder);
@@ -188,20 +442,262 @@ impl Drop for MessageBuilder {
 }
 
 pub struct ValueBuilder<'a> {
-    cache: &'a mut Vec<Vec<u8>>,
+    cache: &'a mut PayloadCache,
     cbuilder: dicey_value_builder,
 }
 
 impl ValueBuilder<'_> {
+    pub(crate) fn pair_with<F, G>(&mut self, first: F, second: G) -> Result<(), Error>
+    where
+        F: FnOnce(&mut ValueBuilder) -> Result<(), Error>,
+        G: FnOnce(&mut ValueBuilder) -> Result<(), Error>,
+    {
+        self.list_with(ListKind::Pair, |items| {
+            items.push(first)?;
+            items.push(second)
+        })
+    }
+
     pub fn set(&mut self, value: impl ToDicey) -> Result<(), Error> {
         value.to_dicey(self)
     }
+
+    /// Sets the value to a map, sent as an array of `{kv}` pairs in the order `entries` yields them.
+    ///
+    /// Unlike a `HashMap`, the order is kept on the wire, for receivers that treat the map as an ordered list.
+    /// Keys and values must be of `key_kind` and `val_kind`; views and owned values are only checked by libdicey.
+    pub fn set_entries<K: ToDicey, V: ToDicey>(
+        &mut self,
+        key_kind: Type,
+        val_kind: Type,
+        entries: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<(), Error> {
+        if !kind_matches(K::TYPE_KIND, key_kind) || !kind_matches(V::TYPE_KIND, val_kind) {
+            return Err(Error::ValueTypeMismatch);
+        }
+
+        self.list_with(ListKind::Array(Type::Pair), |items| {
+            entries.into_iter().try_for_each(|(key, value)| {
+                if items.len >= MAX_LIST_LEN {
+                    return Err(Error::ArrayTooLong);
+                }
+
+                items.push(|item| {
+                    item.pair_with(|first| key.to_dicey(first), |second| value.to_dicey(second))
+                })
+            })
+        })
+    }
+
+    // turns the builder into a list and fills it. The list is closed even if filling it fails, so that the items added
+    // so far become part of the value being built and are freed with it, instead of leaking with the abandoned builder
+    fn list_with<F>(&mut self, kind: ListKind, fill: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut ListItems) -> Result<(), Error>,
+    {
+        unsafe {
+            match kind {
+                ListKind::Array(ty) => {
+                    ccall!(value_builder_array_start, &mut self.cbuilder, ty.to_c())?
+                }
+                ListKind::Pair => ccall!(value_builder_pair_start, &mut self.cbuilder)?,
+                ListKind::Tuple => ccall!(value_builder_tuple_start, &mut self.cbuilder)?,
+            };
+        }
+
+        let mut items = ListItems { list: self, len: 0 };
+
+        let res = fill(&mut items);
+
+        // libdicey refuses to close a pair that lacks an item
+        if res.is_err() && kind == ListKind::Pair {
+            while items.len < 2 && items.push(|item| item.set(())).is_ok() {}
+        }
+
+        let end_res = unsafe {
+            match kind {
+                ListKind::Array(_) => ccall!(value_builder_array_end, &mut self.cbuilder),
+                ListKind::Pair => ccall!(value_builder_pair_end, &mut self.cbuilder),
+                ListKind::Tuple => ccall!(value_builder_tuple_end, &mut self.cbuilder),
+            }
+        };
+
+        res.and(end_res.map(|_| ()))
+    }
+}
+
+// the kind of values whose type is only known at runtime can't be checked in advance
+fn kind_matches(kind: Type, expected: Type) -> bool {
+    kind == Type::Any || kind == expected
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ListKind {
+    Array(Type),
+    Pair,
+    Tuple,
+}
+
+// the items of a list being built
+struct ListItems<'b, 'a> {
+    list: &'b mut ValueBuilder<'a>,
+    len: usize,
+}
+
+impl ListItems<'_, '_> {
+    fn push<F>(&mut self, op: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut ValueBuilder) -> Result<(), Error>,
+    {
+        let mut item = unsafe {
+            let mut item = mem::zeroed();
+
+            ccall!(value_builder_next, &mut self.list.cbuilder, &mut item)?;
+
+            ValueBuilder {
+                cache: self.list.cache,
+                cbuilder: item,
+            }
+        };
+
+        self.len += 1;
+
+        op(&mut item)
+    }
+}
+
+// a value builder that only accepts values of the signature it expects
+pub struct TypedValueBuilder<'b, 'a> {
+    builder: &'b mut ValueBuilder<'a>,
+    expected: &'b Signature,
+}
+
+impl TypedValueBuilder<'_, '_> {
+    pub const fn expected(&self) -> &Signature {
+        self.expected
+    }
+
+    pub fn set(&mut self, value: impl ToDicey) -> Result<(), Error> {
+        if !value.conforms_to(self.expected) {
+            return Err(Error::ValueTypeMismatch);
+        }
+
+        self.builder.set(value)
+    }
+
+    // arrays of variants can't be built, as their items have no common type to declare the array with
+    pub fn array<F>(&mut self, fill: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut TypedListBuilder) -> Result<(), Error>,
+    {
+        match self.expected {
+            Signature::Array(elem) => {
+                let kind = elem.kind().ok_or(Error::ValueTypeMismatch)?;
+
+                self.list(ListKind::Array(kind), fill)
+            }
+            _ => Err(Error::ValueTypeMismatch),
+        }
+    }
+
+    pub fn pair<F>(&mut self, fill: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut TypedListBuilder) -> Result<(), Error>,
+    {
+        match self.expected {
+            Signature::Pair(_, _) => self.list(ListKind::Pair, fill),
+            _ => Err(Error::ValueTypeMismatch),
+        }
+    }
+
+    pub fn tuple<F>(&mut self, fill: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut TypedListBuilder) -> Result<(), Error>,
+    {
+        match self.expected {
+            Signature::Tuple(_) => self.list(ListKind::Tuple, fill),
+            _ => Err(Error::ValueTypeMismatch),
+        }
+    }
+
+    fn list<F>(&mut self, kind: ListKind, fill: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut TypedListBuilder) -> Result<(), Error>,
+    {
+        let expected = self.expected;
+
+        self.builder.list_with(kind, |items| {
+            let mut list = TypedListBuilder { items, expected };
+
+            fill(&mut list)?;
+
+            // tuples and pairs must be filled completely
+            if list.arity().is_some_and(|arity| list.items.len != arity) {
+                return Err(Error::ValueTypeMismatch);
+            }
+
+            Ok(())
+        })
+    }
+}
+
+// the items of a list being built by a TypedValueBuilder
+pub struct TypedListBuilder<'c, 'b, 'a> {
+    items: &'c mut ListItems<'b, 'a>,
+    expected: &'c Signature,
+}
+
+impl TypedListBuilder<'_, '_, '_> {
+    // the signature the next item must have, if the list has room for one
+    pub fn expected_next(&self) -> Option<&Signature> {
+        Self::expected_at(self.expected, self.items.len)
+    }
+
+    pub fn push(&mut self, value: impl ToDicey) -> Result<(), Error> {
+        self.push_with(|item| item.set(value))
+    }
+
+    pub fn push_with<F>(&mut self, op: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut TypedValueBuilder) -> Result<(), Error>,
+    {
+        let expected =
+            Self::expected_at(self.expected, self.items.len).ok_or(Error::ValueTypeMismatch)?;
+
+        self.items
+            .push(|builder| op(&mut TypedValueBuilder { builder, expected }))
+    }
+
+    // the number of items the list must have, if fixed
+    fn arity(&self) -> Option<usize> {
+        match self.expected {
+            Signature::Tuple(sigs) => Some(sigs.len()),
+            Signature::Pair(_, _) => Some(2),
+            _ => None,
+        }
+    }
+
+    fn expected_at(expected: &Signature, n: usize) -> Option<&Signature> {
+        match expected {
+            Signature::Array(elem) => Some(elem),
+            Signature::Tuple(sigs) => sigs.get(n),
+            Signature::Pair(first, _) if n == 0 => Some(first),
+            Signature::Pair(_, second) if n == 1 => Some(second),
+            _ => None,
+        }
+    }
 }
 
 pub trait ToDicey {
     const TYPE_KIND: Type;
 
     fn to_dicey(&self, builder: &mut ValueBuilder) -> Result<(), Error>;
+
+    // whether the value can be sent where `sig` is expected. Only TYPE_KIND is compared by default; containers also
+    // check their contents
+    fn conforms_to(&self, sig: &Signature) -> bool {
+        matches!(sig, Signature::Variant) || sig.kind() == Some(Self::TYPE_KIND)
+    }
 }
 
 macro_rules! impl_to_dicey {
@@ -314,30 +810,28 @@ impl<T: ToDicey> ToDicey for [T] {
     const TYPE_KIND: Type = Type::Array;
 
     fn to_dicey(&self, builder: &mut ValueBuilder) -> Result<(), Error> {
-        unsafe {
-            ccall!(
-                value_builder_array_start,
-                &mut builder.cbuilder,
-                T::TYPE_KIND.to_c()
-            )?;
-
-            for value in self {
-                let mut item = mem::zeroed();
+        if self.len() > MAX_LIST_LEN {
+            return Err(Error::ArrayTooLong);
+        }
 
-                ccall!(value_builder_next, &mut builder.cbuilder, &mut item)?;
+        if T::TYPE_KIND == Type::Any {
+            return Err(Error::ValueTypeMismatch);
+        }
 
-                let mut child = ValueBuilder {
-                    cache: builder.cache,
-                    cbuilder: item,
-                };
+        builder.list_with(ListKind::Array(T::TYPE_KIND), |items| {
+            self.iter()
+                .try_for_each(|value| items.push(|item| value.to_dicey(item)))
+        })
+    }
 
-                value.to_dicey(&mut child)?;
+    fn conforms_to(&self, sig: &Signature) -> bool {
+        match sig {
+            Signature::Variant => true,
+            Signature::Array(elem) => {
+                elem.kind() == Some(T::TYPE_KIND) && self.iter().all(|item| item.conforms_to(elem))
             }
-
-            ccall!(value_builder_array_end, &mut builder.cbuilder)?;
+            _ => false,
         }
-
-        Ok(())
     }
 }
 
@@ -347,82 +841,77 @@ impl<T: ToDicey> ToDicey for Vec<T> {
     fn to_dicey(&self, builder: &mut ValueBuilder) -> Result<(), Error> {
         self.as_slice().to_dicey(builder)
     }
+
+    fn conforms_to(&self, sig: &Signature) -> bool {
+        self.as_slice().conforms_to(sig)
+    }
 }
 
 impl<T: ToDicey, U: ToDicey> ToDicey for (T, U) {
     const TYPE_KIND: Type = Type::Pair;
 
     fn to_dicey(&self, builder: &mut ValueBuilder) -> Result<(), Error> {
-        unsafe {
-            ccall!(value_builder_pair_start, &mut builder.cbuilder)?;
-
-            let mut item = mem::zeroed();
-
-            ccall!(value_builder_next, &mut builder.cbuilder, &mut item)?;
-
-            let mut child = ValueBuilder {
-                cache: builder.cache,
-                cbuilder: item,
-            };
-
-            self.0.to_dicey(&mut child)?;
-
-            ccall!(value_builder_next, &mut builder.cbuilder, &mut item)?;
-
-            let mut child = ValueBuilder {
-                cache: builder.cache,
-                cbuilder: item,
-            };
-
-            self.1.to_dicey(&mut child)?;
+        builder.pair_with(
+            |first| self.0.to_dicey(first),
+            |second| self.1.to_dicey(second),
+        )
+    }
 
-            ccall!(value_builder_pair_end, &mut builder.cbuilder)?;
+    fn conforms_to(&self, sig: &Signature) -> bool {
+        match sig {
+            Signature::Variant => true,
+            Signature::Pair(first, second) => {
+                self.0.conforms_to(first) && self.1.conforms_to(second)
+            }
+            _ => false,
         }
-
-        Ok(())
     }
 }
 
 macro_rules! impl_to_dicey_tuple {
     ($($name:ident)+) => {
-        impl<$($name: ToDicey),+> ToDicey for ($($name,)+) {
+        impl_to_dicey_tuple!(@impl ($($name,)+), ($($name,)+), $($name)+);
+    };
+
+    (@impl $ty:ty, $pat:pat, $($name:ident)+) => {
+        impl<$($name: ToDicey),+> ToDicey for $ty {
             const TYPE_KIND: Type = Type::Tuple;
 
             fn to_dicey(&self, builder: &mut ValueBuilder) -> Result<(), Error> {
                 // we need this, otherwise the hack below won't work
                 #![allow(non_snake_case)]
 
-                let ($($name,)+) = self;
+                let $pat = self;
 
-                unsafe {
-                    ccall!(
-                        value_builder_tuple_start,
-                        &mut builder.cbuilder
-                    )?;
+                builder.list_with(ListKind::Tuple, |items| {
+                    $(items.push(|item| $name.to_dicey(item))?;)+
 
-                    let mut item = mem::zeroed();
+                    Ok(())
+                })
+            }
 
-                    $({
-                        ccall!(value_builder_next, &mut builder.cbuilder, &mut item)?;
+            fn conforms_to(&self, sig: &Signature) -> bool {
+                #![allow(non_snake_case)]
 
-                        let mut child = ValueBuilder {
-                            cache: builder.cache,
-                            cbuilder: item,
-                        };
+                let $pat = self;
 
-                        $name.to_dicey(&mut child)?;
-                    })+;
+                match sig {
+                    Signature::Variant => true,
+                    Signature::Tuple(sigs) => {
+                        let mut sigs = sigs.iter();
 
-                    ccall!(value_builder_tuple_end, &mut builder.cbuilder)?;
+                        $(sigs.next().is_some_and(|sig| $name.conforms_to(sig)) &&)+ sigs.next().is_none()
+                    }
+                    _ => false,
                 }
-
-                Ok(())
             }
         }
     };
 }
 
-// implement ToDicey for tuples of size 1 to 32
+// implement ToDicey for tuples of size 1 to 32. Two-element Rust tuples are pairs, see Tuple2
+impl_to_dicey_tuple!(A);
+impl_to_dicey_tuple!(@impl Tuple2<A, B>, Tuple2(A, B), A B);
 impl_to_dicey_tuple!(A B C);
 impl_to_dicey_tuple!(A B C D);
 impl_to_dicey_tuple!(A B C D E);
@@ -458,9 +947,11 @@ impl ToDicey for [u8] {
     const TYPE_KIND: Type = Type::Bytes;
 
     fn to_dicey(&self, builder: &mut ValueBuilder) -> Result<(), Error> {
-        builder.cache.push(self.to_owned());
+        if self.len() > MAX_BYTES_LEN {
+            return Err(Error::Overflow);
+        }
 
-        let stored_payload = builder.cache.last().unwrap();
+        let stored_payload = builder.cache.push(self.to_owned())?;
 
         unsafe {
             ccall!(
@@ -470,10 +961,7 @@ impl ToDicey for [u8] {
                     type_: dicey_sys::dicey_type_DICEY_TYPE_BYTES,
                     __bindgen_anon_1: dicey_arg__bindgen_ty_1 {
                         bytes: dicey_bytes_arg {
-                            len: stored_payload
-                                .len()
-                                .try_into()
-                                .map_err(|_| Error::Overflow)?,
+                            len: stored_payload.len() as u32,
                             data: stored_payload.as_ptr(),
                         },
                     }
@@ -520,11 +1008,11 @@ impl ToDicey for str {
     const TYPE_KIND: Type = Type::String;
 
     fn to_dicey(&self, builder: &mut ValueBuilder) -> Result<(), Error> {
-        builder.cache.push(
+        let payload = builder.cache.push(
             CString::new(self)
                 .map_err(|_| Error::InvalidData)?
                 .into_bytes_with_nul(),
-        );
+        )?;
 
         unsafe {
             ccall!(
@@ -533,7 +1021,7 @@ impl ToDicey for str {
                 dicey_arg {
                     type_: dicey_sys::dicey_type_DICEY_TYPE_STR,
                     __bindgen_anon_1: dicey_arg__bindgen_ty_1 {
-                        str_: builder.cache.last().unwrap().as_ptr() as *const c_char,
+                        str_: payload.as_ptr() as *const c_char,
                     }
                 }
             )
@@ -578,7 +1066,7 @@ impl ToDicey for CStr {
     const TYPE_KIND: Type = Type::String;
 
     fn to_dicey(&self, builder: &mut ValueBuilder) -> Result<(), Error> {
-        builder.cache.push(self.to_bytes_with_nul().to_owned());
+        let payload = builder.cache.push(self.to_bytes_with_nul().to_owned())?;
 
         unsafe {
             ccall!(
@@ -587,7 +1075,7 @@ impl ToDicey for CStr {
                 dicey_arg {
                     type_: dicey_sys::dicey_type_DICEY_TYPE_STR,
                     __bindgen_anon_1: dicey_arg__bindgen_ty_1 {
-                        str_: builder.cache.last().unwrap().as_ptr() as *const c_char,
+                        str_: payload.as_ptr() as *const c_char,
                     }
                 }
             )
@@ -608,11 +1096,9 @@ impl ToDicey for &'_ Path {
     const TYPE_KIND: Type = Type::Path;
 
     fn to_dicey(&self, builder: &mut ValueBuilder) -> Result<(), Error> {
-        builder.cache.push(
-            CString::new(self as &str)
-                .map_err(|_| Error::InvalidData)?
-                .into_bytes_with_nul(),
-        );
+        let payload = builder
+            .cache
+            .push(bytes_to_cstring(self.as_bytes())?.into_bytes_with_nul())?;
 
         unsafe {
             ccall!(
@@ -621,7 +1107,7 @@ impl ToDicey for &'_ Path {
                 dicey_arg {
                     type_: dicey_sys::dicey_type_DICEY_TYPE_PATH,
                     __bindgen_anon_1: dicey_arg__bindgen_ty_1 {
-                        str_: builder.cache.last().unwrap().as_ptr() as *const c_char,
+                        str_: payload.as_ptr() as *const c_char,
                     }
                 }
             )
@@ -634,25 +1120,16 @@ impl ToDicey for Selector<'_> {
     const TYPE_KIND: Type = Type::Selector;
 
     fn to_dicey(&self, builder: &mut ValueBuilder) -> Result<(), Error> {
-        builder.cache.push(
-            CString::new(self.trait_name)
-                .map_err(|_| Error::InvalidData)?
-                .into_bytes_with_nul(),
-        );
-
-        builder.cache.push(
-            CString::new(self.elem)
-                .map_err(|_| Error::InvalidData)?
-                .into_bytes_with_nul(),
-        );
+        // the payloads stay where they are in memory as the cache grows
+        let trait_name = builder
+            .cache
+            .push(bytes_to_cstring(self.trait_name)?.into_bytes_with_nul())?
+            .as_ptr() as *const c_char;
 
-        let (trait_name, elem) = match &builder.cache[builder.cache.len() - 2..] {
-            [tname, elem] => (
-                tname.as_ptr() as *const c_char,
-                elem.as_ptr() as *const c_char,
-            ),
-            _ => unreachable!(),
-        };
+        let elem = builder
+            .cache
+            .push(bytes_to_cstring(self.elem)?.into_bytes_with_nul())?
+            .as_ptr() as *const c_char;
 
         let arg = dicey_arg {
             type_: dicey_sys::dicey_type_DICEY_TYPE_SELECTOR,
@@ -668,18 +1145,27 @@ impl ToDicey for Selector<'_> {
     }
 }
 
+impl ToDicey for SelectorBuf {
+    const TYPE_KIND: Type = Type::Selector;
+
+    fn to_dicey(&self, builder: &mut ValueBuilder) -> Result<(), Error> {
+        self.as_selector().to_dicey(builder)
+    }
+}
+
 impl ToDicey for ErrorMessage<'_> {
     const TYPE_KIND: Type = Type::Error;
 
     fn to_dicey(&self, builder: &mut ValueBuilder) -> Result<(), Error> {
         let message = if self.message.is_some() {
-            builder.cache.push(
-                CString::new(self.message.unwrap())
-                    .map_err(|_| Error::InvalidData)?
-                    .into_bytes_with_nul(),
-            );
-
-            builder.cache.last().unwrap().as_ptr() as *const c_char
+            builder
+                .cache
+                .push(
+                    CString::new(self.message.unwrap())
+                        .map_err(|_| Error::InvalidData)?
+                        .into_bytes_with_nul(),
+                )?
+                .as_ptr() as *const c_char
         } else {
             std::ptr::null()
         };
@@ -703,10 +1189,358 @@ impl ToDicey for ErrorMessage<'_> {
     }
 }
 
-fn strip_null(bytes: &[u8]) -> &[u8] {
-    if bytes.ends_with(&[0]) {
-        &bytes[..bytes.len() - 1]
-    } else {
-        bytes
+// a view's type is only known at runtime. Slices of views can't tell libdicey their element type, and are rejected; use
+// ValueView::Array instead, which carries it
+impl ToDicey for ValueView<'_> {
+    const TYPE_KIND: Type = Type::Any;
+
+    fn to_dicey(&self, builder: &mut ValueBuilder) -> Result<(), Error> {
+        match self {
+            ValueView::Unit => ().to_dicey(builder),
+            ValueView::Bool(b) => b.to_dicey(builder),
+            ValueView::Byte(b) => b.to_dicey(builder),
+            ValueView::Float(f) => f.to_dicey(builder),
+            ValueView::Int16(i) => i.to_dicey(builder),
+            ValueView::Int32(i) => i.to_dicey(builder),
+            ValueView::Int64(i) => i.to_dicey(builder),
+            ValueView::UInt16(u) => u.to_dicey(builder),
+            ValueView::UInt32(u) => u.to_dicey(builder),
+            ValueView::UInt64(u) => u.to_dicey(builder),
+            ValueView::Array {
+                element_kind,
+                items,
+            } => builder.list_of_views(Some(*element_kind), items),
+            ValueView::Tuple(items) => builder.list_of_views(None, items),
+            ValueView::Pair(first, second) => builder.pair_with(
+                |builder| first.to_dicey(builder),
+                |builder| second.to_dicey(builder),
+            ),
+            ValueView::Bytes(bytes) => bytes.to_dicey(builder),
+            ValueView::String(s) => s.to_dicey(builder),
+
+            ValueView::Uuid(uuid) => uuid.to_dicey(builder),
+            ValueView::Path(path) => path.to_dicey(builder),
+            ValueView::Selector(sel) => sel.to_dicey(builder),
+            ValueView::Error(err) => err.to_dicey(builder),
+
+            // there are no contents to replay
+            ValueView::Unknown { .. } => Err(Error::ValueTypeMismatch),
+        }
+    }
+
+    fn conforms_to(&self, sig: &Signature) -> bool {
+        sig.matches(self)
+    }
+}
+
+impl ToDicey for OwnedValue {
+    const TYPE_KIND: Type = <ValueView as ToDicey>::TYPE_KIND;
+
+    fn to_dicey(&self, builder: &mut ValueBuilder) -> Result<(), Error> {
+        self.as_view().to_dicey(builder)
+    }
+
+    fn conforms_to(&self, sig: &Signature) -> bool {
+        self.as_view().conforms_to(sig)
+    }
+}
+
+impl ValueBuilder<'_> {
+    // builds an array out of views whose type is only known at runtime, such as the items of a received array
+    pub fn set_view_array(&mut self, element_kind: Type, items: &[ValueView]) -> Result<(), Error> {
+        if items.iter().any(|item| item.kind() != element_kind) {
+            return Err(Error::ValueTypeMismatch);
+        }
+
+        self.list_of_views(Some(element_kind), items)
+    }
+
+    // builds an array of the given kind, or a tuple if there's none
+    fn list_of_views(&mut self, kind: Option<Type>, items: &[ValueView]) -> Result<(), Error> {
+        if items.len() > MAX_LIST_LEN {
+            return Err(Error::ArrayTooLong);
+        }
+
+        let kind = kind.map_or(ListKind::Tuple, ListKind::Array);
+
+        self.list_with(kind, |list| {
+            items
+                .iter()
+                .try_for_each(|value| list.push(|item| value.to_dicey(item)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::FromDicey;
+
+    fn set() -> MessageBuilder<SetOp> {
+        MessageBuilder::set()
+            .unwrap()
+            .path("/foo")
+            .unwrap()
+            .selector(("a.Trait", "Prop"))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_seq() {
+        let builder = MessageBuilder::get()
+            .unwrap()
+            .path("/foo")
+            .unwrap()
+            .selector(("a.Trait", "Prop"))
+            .unwrap()
+            .seq(42);
+
+        assert_eq!(builder.current_seq(), Some(42));
+        assert_eq!(builder.build().unwrap().seq(), 42);
+    }
+
+    #[test]
+    fn test_value_with_key() {
+        let key = Uuid::from_u128(42);
+
+        let msg = set()
+            .value_with_key(Some(key), |builder| builder.set(1u32))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            msg.value(),
+            Some(ValueView::pair(ValueView::Uuid(key), ValueView::UInt32(1)))
+        );
+
+        let msg = set()
+            .value_with_key(None, |builder| builder.set(1u32))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(msg.value(), Some(ValueView::UInt32(1)));
+    }
+
+    #[test]
+    fn test_limits() {
+        let res = set().value(vec![(); MAX_LIST_LEN + 1]);
+
+        assert!(matches!(res, Err(Error::ArrayTooLong)));
+    }
+
+    #[test]
+    fn test_set_entries() {
+        let entries = vec![
+            ("z".to_string(), 1i64),
+            ("a".to_string(), 2),
+            ("m".to_string(), 3),
+        ];
+
+        let msg = set()
+            .value_with(|builder| builder.set_entries(Type::String, Type::Int64, entries.clone()))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let value = msg.value().unwrap();
+        assert!(Signature::parse("[{sx}]").unwrap().matches(&value));
+        assert_eq!(Vec::<(String, i64)>::from_dicey(&value).unwrap(), entries);
+
+        let res =
+            set().value_with(|builder| builder.set_entries(Type::String, Type::Int32, entries));
+        assert!(matches!(res, Err(Error::ValueTypeMismatch)));
+    }
+
+    #[test]
+    fn test_cache_limit() {
+        // strings are cached with their terminator, and neither the path nor the selector count
+        let builder = set().value(vec!["abc", "de"]).unwrap();
+        assert_eq!(builder.cached_bytes(), 7);
+        assert!(builder.build().is_ok());
+
+        let builder = set().with_cache_limit(7).value(vec!["abc", "de"]).unwrap();
+        assert_eq!(builder.cached_bytes(), 7);
+
+        let res = set()
+            .with_cache_limit(1024)
+            .value((vec![0u8; 1000], "x".repeat(100)));
+        assert!(matches!(res, Err(Error::Overflow)));
+
+        // nothing left behind trips up the next message
+        assert!(set().value("ok").unwrap().build().is_ok());
+    }
+
+    #[test]
+    fn test_failed_value_is_discarded() {
+        // the closure's own error is reported, not one from closing the half-built lists
+        let res = set().value_with(|builder| builder.set(vec!["ok", "not\0ok", "never"]));
+        assert!(matches!(res, Err(Error::InvalidData)));
+
+        let res = set().value_with(|builder| {
+            builder.set(vec![
+                (1u32, vec![Tuple2("a", "b")]),
+                (2u32, vec![Tuple2("c", "\0")]),
+            ])
+        });
+        assert!(matches!(res, Err(Error::InvalidData)));
+
+        let res =
+            set().value_with(|builder| builder.pair_with(|_| Err(Error::Overflow), |_| Ok(())));
+        assert!(matches!(res, Err(Error::Overflow)));
+
+        // nothing left behind trips up the next message
+        assert!(set().value(vec!["ok"]).unwrap().build().is_ok());
+    }
+
+    #[test]
+    fn test_value_for_signature() {
+        let exec = || {
+            MessageBuilder::exec()
+                .unwrap()
+                .path("/foo")
+                .unwrap()
+                .selector(("a.Trait", "Op"))
+                .unwrap()
+        };
+
+        let msg = exec()
+            .value_for_signature("(s[u]{bv}) -> $", |builder| {
+                builder.tuple(|items| {
+                    assert_eq!(items.expected_next(), Some(&Signature::String));
+                    items.push("hello")?;
+                    items.push(vec![1u32, 2])?;
+                    items.push_with(|pair| {
+                        pair.pair(|items| {
+                            items.push(true)?;
+                            items.push(Selector::from(("a.Trait", "Prop")))
+                        })
+                    })
+                })
+            })
+            .unwrap()
+            .build()
+            .unwrap();
+        assert!(
+            Signature::parse("(s[u]{bv})")
+                .unwrap()
+                .matches(&msg.value().unwrap())
+        );
+
+        // plain values are checked as a whole
+        assert!(
+            exec()
+                .value_for_signature("[s]", |b| b.set(vec!["a"]))
+                .is_ok()
+        );
+
+        for res in [
+            exec().value_for_signature("[s]", |b| b.set(vec![1u32])),
+            exec().value_for_signature("(sb)", |b| b.set(("a", 1u32, true))),
+            exec().value_for_signature("(sb)", |b| b.tuple(|items| items.push("a"))),
+            exec().value_for_signature("{sb}", |b| {
+                b.pair(|items| {
+                    items.push("a")?;
+                    items.push(false)?;
+                    items.push(true)
+                })
+            }),
+            exec().value_for_signature("[v]", |b| b.array(|_| Ok(()))),
+        ] {
+            assert!(matches!(res, Err(Error::ValueTypeMismatch)));
+        }
+    }
+
+    #[test]
+    fn test_selector_cstr() {
+        let builder = MessageBuilder::get()
+            .unwrap()
+            .path("/foo")
+            .unwrap()
+            .selector_cstr(c"a.Trait", c"Prop")
+            .unwrap();
+        assert_eq!(builder.current_selector(), Some(("a.Trait", "Prop").into()));
+
+        let msg = builder.build().unwrap();
+        assert_eq!(msg.selector(), ("a.Trait", "Prop").into());
+    }
+
+    #[test]
+    fn test_dynamic_op() {
+        let res = MessageBuilder::new(Op::Get).unwrap().value(42u32);
+        assert!(matches!(res, Err(Error::InvalidData)));
+
+        let msg = MessageBuilder::get()
+            .unwrap()
+            .path("/foo")
+            .unwrap()
+            .into_dynamic()
+            .selector(("a.Trait", "Prop"))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(msg.path(), "/foo");
+        assert!(msg.value().is_none());
+    }
+
+    #[test]
+    fn test_set_view_array() {
+        let items = [ValueView::String("a"), ValueView::String("b")];
+
+        let msg = set()
+            .value_with(|builder| builder.set_view_array(Type::String, &items))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            msg.value().unwrap().extract::<Vec<&str>>().unwrap(),
+            ["a", "b"]
+        );
+
+        let res = MessageBuilder::set().unwrap().value_with(|builder| {
+            builder.set_view_array(
+                Type::String,
+                &[ValueView::String("a"), ValueView::Bool(true)],
+            )
+        });
+        assert!(matches!(res, Err(Error::ValueTypeMismatch)));
+
+        // a list of views has no element kind to give libdicey
+        let res = MessageBuilder::set().unwrap().value(items.to_vec());
+        assert!(matches!(res, Err(Error::ValueTypeMismatch)));
+    }
+
+    #[test]
+    fn test_forward_view() {
+        let build = |value: &dyn Fn(&mut ValueBuilder) -> Result<(), Error>| {
+            set().value_with(value).unwrap().build().unwrap()
+        };
+
+        let original = build(&|builder| {
+            builder.set((
+                vec![Tuple2(1u16, "a"), Tuple2(2u16, "b")],
+                Tuple2(
+                    Selector::from(("b.Other", "Elem")),
+                    ErrorMessage {
+                        code: -3,
+                        message: Some("oops"),
+                    },
+                ),
+            ))
+        });
+
+        let view = original.value().unwrap();
+
+        let forwarded = build(&|builder| builder.set(view.clone()));
+        assert_eq!(forwarded.value(), Some(view.clone()));
+
+        let owned = OwnedValue::from(&view);
+        let forwarded = build(&|builder| builder.set(owned.clone()));
+        assert_eq!(forwarded.raw_bytes(), original.raw_bytes());
+
+        let res = MessageBuilder::set()
+            .unwrap()
+            .value(ValueView::Unknown { type_byte: b'?' });
+        assert!(matches!(res, Err(Error::ValueTypeMismatch)));
     }
 }
@@ -17,3 +17,149 @@
 #![allow(clippy::enum_variant_names)]
 
 include!(concat!(env!("OUT_DIR"), "/errors.rs"));
+
+use std::fmt;
+
+// broadly, where an error comes from
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ErrorSource {
+    // the server understood the request and refused it
+    Application,
+
+    // the connection couldn't be established or was lost
+    Transport,
+
+    Timeout,
+
+    // the peers couldn't make sense of each other
+    Protocol,
+
+    // the request never left this process, e.g. because a value couldn't be encoded or the client was closed
+    Local,
+
+    // not an error at all, only there because `Error::OK` is part of the error table
+    Success,
+}
+
+impl Error {
+    // the mapping is exhaustive on purpose, so that codes added to libdicey must be classified before they're usable
+    pub const fn source_kind(self) -> ErrorSource {
+        match self {
+            Error::ElementNotFound
+            | Error::NotSupported
+            | Error::ObjectExists
+            | Error::PathDeleted
+            | Error::PathNotAlias
+            | Error::PathNotFound
+            | Error::PeerNotFound
+            | Error::PermissionDenied
+            | Error::PropertyReadOnly
+            | Error::SignatureMismatch
+            | Error::TraitNotFound => ErrorSource::Application,
+
+            Error::AddressInUse
+            | Error::BadFileDescriptor
+            | Error::BrokenPipe
+            | Error::ConnectionRefused
+            | Error::ConnectionReset
+            | Error::UnknownUVError => ErrorSource::Transport,
+
+            Error::OK => ErrorSource::Success,
+
+            Error::TimedOut => ErrorSource::Timeout,
+
+            Error::BadMessage
+            | Error::ClientTooOld
+            | Error::SequenceNumberMismatch
+            | Error::ServerTooOld => ErrorSource::Protocol,
+
+            Error::Already
+            | Error::ArrayTooLong
+            | Error::Cancelled
+            | Error::FileNotFound
+            | Error::InvalidData
+            | Error::InvalidPluginName
+            | Error::MalformedPath
+            | Error::MalformedSignature
+            | Error::NoDataAvailable
+            | Error::NotADirectory
+            | Error::OutOfMemory
+            | Error::Overflow
+            | Error::PathTooLong
+            | Error::TryAgain
+            | Error::TupleTooLong
+            | Error::UUIDNotValid
+            | Error::ValueTypeMismatch => ErrorSource::Local,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DiceyError {
+    kind: Error,
+    source: Option<Box<dyn std::error::Error + Send + Sync>>,
+}
+
+impl DiceyError {
+    pub const fn new(kind: Error) -> Self {
+        Self { kind, source: None }
+    }
+
+    pub fn with_source(
+        kind: Error,
+        source: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+    ) -> Self {
+        Self {
+            kind,
+            source: Some(source.into()),
+        }
+    }
+
+    pub const fn kind(&self) -> Error {
+        self.kind
+    }
+}
+
+impl fmt::Display for DiceyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.source {
+            Some(source) => write!(f, "{}: {source}", self.kind),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+impl std::error::Error for DiceyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_deref()
+            .map(|err| err as &(dyn std::error::Error + 'static))
+    }
+}
+
+impl From<Error> for DiceyError {
+    fn from(kind: Error) -> Self {
+        Self::new(kind)
+    }
+}
+
+impl From<DiceyError> for Error {
+    fn from(err: DiceyError) -> Self {
+        err.kind
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_kind() {
+        assert_eq!(Error::TraitNotFound.source_kind(), ErrorSource::Application);
+        assert_eq!(Error::ConnectionReset.source_kind(), ErrorSource::Transport);
+        assert_eq!(Error::TimedOut.source_kind(), ErrorSource::Timeout);
+        assert_eq!(Error::ServerTooOld.source_kind(), ErrorSource::Protocol);
+        assert_eq!(Error::ValueTypeMismatch.source_kind(), ErrorSource::Local);
+        assert_eq!(Error::OK.source_kind(), ErrorSource::Success);
+    }
+}
@@ -0,0 +1,225 @@
+/*
+ * Copyright (c) 2014-2024 Zuru Tech HK Limited, All rights reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use serde::{
+    Serialize, Serializer,
+    ser::{SerializeMap, SerializeStruct},
+};
+use serde_json::Value;
+
+use super::{
+    errors::Error,
+    owned::OwnedValue,
+    value::{Type, ValueView},
+};
+
+/// Values serialize into the data model as follows:
+/// - unit is a unit, booleans, numbers and strings map to themselves;
+/// - arrays, tuples and pairs are all sequences. Arrays of pairs whose first elements are all strings are maps
+///   instead, which is how [`OwnedValue::from_json`] encodes JSON objects;
+/// - bytes are bytes, UUIDs their hyphenated string form, paths strings, and selectors strings in the `trait:elem`
+///   form;
+/// - errors are structs with a `code` and a `message` field;
+/// - values of unknown types are units.
+impl Serialize for ValueView<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ValueView::Unit | ValueView::Unknown { .. } => serializer.serialize_unit(),
+            ValueView::Bool(b) => serializer.serialize_bool(*b),
+            ValueView::Byte(b) => serializer.serialize_u8(b.0),
+            ValueView::Float(f) => serializer.serialize_f64(*f),
+            ValueView::Int16(i) => serializer.serialize_i16(*i),
+            ValueView::Int32(i) => serializer.serialize_i32(*i),
+            ValueView::Int64(i) => serializer.serialize_i64(*i),
+            ValueView::UInt16(u) => serializer.serialize_u16(*u),
+            ValueView::UInt32(u) => serializer.serialize_u32(*u),
+            ValueView::UInt64(u) => serializer.serialize_u64(*u),
+
+            ValueView::Array {
+                element_kind: Type::Pair,
+                items,
+            } if items
+                .iter()
+                .all(|item| matches!(item, ValueView::Pair(key, _) if matches!(**key, ValueView::String(_)))) =>
+            {
+                let mut map = serializer.serialize_map(Some(items.len()))?;
+
+                for item in items {
+                    if let ValueView::Pair(key, value) = item {
+                        map.serialize_entry(key, value)?;
+                    }
+                }
+
+                map.end()
+            }
+
+            ValueView::Array { items, .. } | ValueView::Tuple(items) => serializer.collect_seq(items),
+            ValueView::Pair(first, second) => serializer.collect_seq([first, second]),
+
+            ValueView::Bytes(bytes) => serializer.serialize_bytes(bytes),
+            ValueView::String(s) => serializer.serialize_str(s),
+            ValueView::Uuid(uuid) => serializer.collect_str(&uuid.hyphenated()),
+            ValueView::Path(path) => serializer.serialize_str(path),
+            ValueView::Selector(sel) => serializer.collect_str(&format_args!(
+                "{}:{}",
+                String::from_utf8_lossy(sel.trait_name),
+                String::from_utf8_lossy(sel.elem)
+            )),
+            ValueView::Error(err) => {
+                let mut error = serializer.serialize_struct("ErrorMessage", 2)?;
+
+                error.serialize_field("code", &err.code)?;
+                error.serialize_field("message", &err.message)?;
+
+                error.end()
+            }
+        }
+    }
+}
+
+impl Serialize for OwnedValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.as_view().serialize(serializer)
+    }
+}
+
+impl ValueView<'_> {
+    /// Converts this value into JSON, following its [`Serialize`] implementation.
+    ///
+    /// Units become `null`, and so do non-finite floats, given JSON can't represent them. Bytes become arrays of
+    /// numbers.
+    pub fn to_json(&self) -> Value {
+        // maps only ever have string keys, which is all serde_json could fail on
+        serde_json::to_value(self).expect("dicey values always convert to JSON")
+    }
+}
+
+impl OwnedValue {
+    /// Converts a JSON value into a dicey value, the reverse of [`ValueView::to_json`].
+    ///
+    /// `null` becomes unit, integers become `Int64` (or `UInt64` when they don't fit) and other numbers become floats.
+    /// JSON arrays become dicey arrays if all their items share the same kind, and tuples otherwise; empty arrays become
+    /// empty arrays of units. Objects become arrays of `{sv}` pairs. Fails with [`Error::InvalidData`] on strings
+    /// containing NUL characters, which dicey can't represent.
+    pub fn from_json(value: &Value) -> Result<OwnedValue, Error> {
+        Ok(match value {
+            Value::Null => OwnedValue::Unit,
+            Value::Bool(b) => OwnedValue::Bool(*b),
+            Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+                (Some(i), _) => OwnedValue::Int64(i),
+                (None, Some(u)) => OwnedValue::UInt64(u),
+                (None, None) => OwnedValue::Float(n.as_f64().ok_or(Error::InvalidData)?),
+            },
+            Value::String(s) => OwnedValue::String(json_string(s)?),
+            Value::Array(items) => {
+                let items = items
+                    .iter()
+                    .map(OwnedValue::from_json)
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                match items.first().map(OwnedValue::kind) {
+                    None => OwnedValue::Array {
+                        element_kind: Type::Unit,
+                        items,
+                    },
+                    Some(kind) if items.iter().all(|item| item.kind() == kind) => {
+                        OwnedValue::Array {
+                            element_kind: kind,
+                            items,
+                        }
+                    }
+                    Some(_) => OwnedValue::Tuple(items),
+                }
+            }
+            Value::Object(object) => OwnedValue::Array {
+                element_kind: Type::Pair,
+                items: object
+                    .iter()
+                    .map(|(key, value)| {
+                        Ok(OwnedValue::Pair(
+                            Box::new(OwnedValue::String(json_string(key)?)),
+                            Box::new(OwnedValue::from_json(value)?),
+                        ))
+                    })
+                    .collect::<Result<_, Error>>()?,
+            },
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        self.as_view().to_json()
+    }
+}
+
+fn json_string(s: &str) -> Result<String, Error> {
+    if s.contains('\0') {
+        Err(Error::InvalidData)
+    } else {
+        Ok(s.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::Map;
+
+    use crate::Selector;
+
+    #[test]
+    fn test_json_roundtrip() {
+        let json = Value::Object(Map::from_iter([
+            ("name".to_owned(), Value::String("dicey".to_owned())),
+            ("answer".to_owned(), Value::from(42)),
+            (
+                "mixed".to_owned(),
+                Value::Array(vec![Value::Bool(true), Value::Null, Value::from(-1)]),
+            ),
+            (
+                "same".to_owned(),
+                Value::Array(vec![Value::from(1), Value::from(2)]),
+            ),
+        ]));
+
+        let value = OwnedValue::from_json(&json).unwrap();
+
+        assert_eq!(value.to_json(), json);
+
+        assert_eq!(
+            OwnedValue::from_json(&Value::String("nul\0".to_owned())),
+            Err(Error::InvalidData)
+        );
+    }
+
+    #[test]
+    fn test_serialize() {
+        let value = OwnedValue::Tuple(vec![
+            OwnedValue::Float(f64::NAN),
+            OwnedValue::Bytes(vec![1, 2]),
+            OwnedValue::Selector(Selector::from(("a.Trait", "Elem")).into()),
+            OwnedValue::Error {
+                code: -3,
+                message: None,
+            },
+        ]);
+
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            r#"[null,[1,2],"a.Trait:Elem",{"code":-3,"message":null}]"#
+        );
+    }
+}
@@ -22,13 +22,20 @@ pub(crate) mod errors;
 pub(crate) mod macros;
 pub(crate) mod value;
 
+mod abi;
 mod builder;
+#[cfg(feature = "serde")]
+mod json;
+mod owned;
+mod signature;
+mod time;
 
 use std::{
-    ffi::c_void,
+    borrow::Cow,
+    ffi::{CStr, c_void},
     fmt, io,
     mem::{self, ManuallyDrop},
-    path::Path,
+    path, slice,
 };
 
 
@@ -44,13 +51,27 @@ use dicey_sys::{
 };
 
 pub use self::{
-    builder::{MessageBuilder, ToDicey, ValueBuilder},
-    errors::Error,
-    value::{ErrorMessage, FromDicey, Selector, ValueView},
+    abi::check_abi,
+    builder::{
+        AnyOp, BuildOp, EventOp, ExecOp, GetOp, MessageBuilder, ResponseOp, SetOp, ToDicey,
+        TypedListBuilder, TypedValueBuilder, ValueBuilder, ValueOp, ValuedSetOp,
+    },
+    errors::{DiceyError, Error},
+    owned::{OwnedMessage, OwnedValue},
+    signature::{Signature, SignatureMismatch},
+    value::{
+        Byte, ErrorMessage, FromDicey, Index, ListIter, Path, PathBuf, Primitive, Selector,
+        SelectorBuf, Tuple2, Type, ValueView,
+    },
 };
 
 use self::macros::ccall;
 
+pub use dicey_sys::{
+    DICEY_MAX_BYTES_LEN as MAX_BYTES_LEN, DICEY_MAX_LIST_LEN as MAX_LIST_LEN,
+    DICEY_MAX_PACKET_LEN as MAX_PACKET_LEN, DICEY_MAX_PATH_LEN as MAX_PATH_LEN,
+};
+
 #[derive(Debug)]
 pub enum Packet {
     Bye(Bye),
@@ -60,6 +81,12 @@ pub enum Packet {
 
 impl Packet {
     pub fn load(bytes: &[u8]) -> Result<Self, Error> {
+        check_abi()?;
+
+        if bytes.len() as u64 > MAX_PACKET_LEN {
+            return Err(Error::Overflow);
+        }
+
         let pw = RawPacket::load(bytes)?;
 
         match pw.op() {
@@ -72,16 +99,81 @@ impl Packet {
         }
     }
 
-    pub fn load_from(mut read: impl io::Read) -> Result<Self, Error> {
+    pub fn load_from(read: impl io::Read) -> Result<Self, Error> {
+        Self::load_from_with_source(read).map_err(Error::from)
+    }
+
+    /// Like [`Packet::load_from`], but I/O errors keep the [`io::Error`] that caused them as their source.
+    pub fn load_from_with_source(mut read: impl io::Read) -> Result<Self, DiceyError> {
         let mut bytes = Vec::new();
 
         read.read_to_end(&mut bytes).map_err(map_io_error)?;
 
-        Self::load(&bytes)
+        Ok(Self::load(&bytes)?)
+    }
+
+    pub fn load_path(path: impl AsRef<path::Path>) -> Result<Self, Error> {
+        Self::load_path_with_source(path).map_err(Error::from)
+    }
+
+    /// Like [`Packet::load_path`], but I/O errors keep the [`io::Error`] that caused them as their source.
+    pub fn load_path_with_source(path: impl AsRef<path::Path>) -> Result<Self, DiceyError> {
+        Ok(Self::load(&std::fs::read(path).map_err(map_io_error)?)?)
+    }
+
+    pub const fn as_message(&self) -> Option<&Message> {
+        match self {
+            Packet::Message(m) => Some(m),
+            _ => None,
+        }
+    }
+
+    // the whole packet is still loaded and validated by libdicey, only to be dropped right after, so this is no cheaper
+    // than load; it only spares the caller matching on the result
+    pub fn classify(bytes: &[u8]) -> Result<PacketKind, Error> {
+        check_abi()?;
+
+        PacketKind::try_from(RawPacket::load(bytes)?.op())
     }
 
-    pub fn load_path(path: impl AsRef<Path>) -> Result<Self, Error> {
-        Self::load(&std::fs::read(path).map_err(map_io_error)?)
+    pub const fn kind(&self) -> PacketKind {
+        match self {
+            Packet::Bye(_) => PacketKind::Bye,
+            Packet::Hello(_) => PacketKind::Hello,
+            Packet::Message(_) => PacketKind::Message,
+        }
+    }
+
+    /// Builds a message packet in one go, e.g. for test fixtures or replay tools.
+    ///
+    /// Get requests are the only ones without a value, and must be given `None`; every other op needs one.
+    pub fn message<'a>(
+        op: Op,
+        seq: u32,
+        path: &str,
+        selector: impl Into<Selector<'a>>,
+        value: Option<impl ToDicey>,
+    ) -> Result<Packet, Error> {
+        let builder = MessageBuilder::new(op)?
+            .seq(seq)
+            .path(path)?
+            .selector(selector)?;
+
+        let builder = match value {
+            Some(value) => builder.value(value)?,
+            None if op == Op::Get => builder,
+            None => return Err(Error::InvalidData),
+        };
+
+        builder.build().map(Packet::Message)
+    }
+
+    pub fn raw_bytes(&self) -> &[u8] {
+        match self {
+            Packet::Bye(b) => b.rpacket.as_bytes(),
+            Packet::Hello(h) => h.rpacket.as_bytes(),
+            Packet::Message(m) => m.raw_bytes(),
+        }
     }
 
     pub fn seq(&self) -> u32 {
@@ -93,6 +185,12 @@ impl Packet {
     }
 }
 
+impl From<Message> for Packet {
+    fn from(message: Message) -> Self {
+        Packet::Message(message)
+    }
+}
+
 pub struct Bye {
     rpacket: RawPacket,
 
@@ -143,6 +241,26 @@ impl fmt::Debug for Bye {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PacketKind {
+    Bye,
+    Hello,
+    Message,
+}
+
+impl TryFrom<dicey_packet_kind> for PacketKind {
+    type Error = Error;
+
+    fn try_from(value: dicey_packet_kind) -> Result<Self, Error> {
+        match value {
+            dicey_packet_kind_DICEY_PACKET_KIND_BYE => Ok(PacketKind::Bye),
+            dicey_packet_kind_DICEY_PACKET_KIND_HELLO => Ok(PacketKind::Hello),
+            dicey_packet_kind_DICEY_PACKET_KIND_MESSAGE => Ok(PacketKind::Message),
+            _ => Err(Error::InvalidData),
+        }
+    }
+}
+
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum ByeReason {
@@ -162,6 +280,7 @@ impl TryFrom<dicey_bye_reason> for ByeReason {
     }
 }
 
+#[derive(Clone)]
 pub struct Event(RawMessage);
 
 impl Event {
@@ -169,7 +288,7 @@ impl Event {
         self.0.seq()
     }
 
-    pub fn path(&self) -> &str {
+    pub fn path(&self) -> Cow<'_, str> {
         self.0.path()
     }
 
@@ -181,6 +300,10 @@ impl Event {
         self.0.value()
     }
 
+    pub fn value_array_iter(&self) -> Result<ListIter<'_>, Error> {
+        self.0.value_array_iter()
+    }
+
     pub(crate) fn into_raw(self) -> dicey_packet {
         self.0.into_raw()
     }
@@ -192,6 +315,7 @@ impl fmt::Debug for Event {
     }
 }
 
+#[derive(Clone)]
 pub struct Exec(RawMessage);
 
 impl Exec {
@@ -199,7 +323,7 @@ impl Exec {
         self.0.seq()
     }
 
-    pub fn path(&self) -> &str {
+    pub fn path(&self) -> Cow<'_, str> {
         self.0.path()
     }
 
@@ -211,6 +335,10 @@ impl Exec {
         self.0.value()
     }
 
+    pub fn value_array_iter(&self) -> Result<ListIter<'_>, Error> {
+        self.0.value_array_iter()
+    }
+
     pub(crate) fn into_raw(self) -> dicey_packet {
         self.0.into_raw()
     }
@@ -222,6 +350,7 @@ impl fmt::Debug for Exec {
     }
 }
 
+#[derive(Clone)]
 pub struct Get(RawMessage);
 
 impl Get {
@@ -229,7 +358,7 @@ impl Get {
         self.0.seq()
     }
 
-    pub fn path(&self) -> &str {
+    pub fn path(&self) -> Cow<'_, str> {
         self.0.path()
     }
 
@@ -291,7 +420,29 @@ impl fmt::Debug for Hello {
     }
 }
 
-#[derive(Debug)]
+struct TruncatedMessage<'a> {
+    msg: &'a Message,
+    max_len: usize,
+}
+
+impl fmt::Debug for TruncatedMessage<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct(&format!("{:?}", self.msg.op()));
+
+        s.field("seq", &self.msg.seq())
+            .field("path", &self.msg.path())
+            .field("selector", &self.msg.selector());
+
+        if let Some(value) = self.msg.value() {
+            s.field("value", &value.debug_truncated(self.max_len));
+        }
+
+        s.finish()
+    }
+}
+
+// cloning a message is a full copy of its payload, which then also gets validated again as if it came from the wire
+#[derive(Clone, Debug)]
 pub enum Message {
     Event(Event),
     Exec(Exec),
@@ -311,13 +462,33 @@ impl Message {
         }
     }
 
-    pub fn path(&self) -> &str {
+    // like Debug, but showing at most `max_len` items of each bytes, string or list value, see ValueView::debug_truncated
+    pub fn debug_truncated(&self, max_len: usize) -> impl fmt::Debug {
+        TruncatedMessage { msg: self, max_len }
+    }
+
+    pub fn into_owned(self) -> OwnedMessage {
+        OwnedMessage::from(&self)
+    }
+
+    // extracts a reply's value as T, turning error replies into errors. T can't borrow from the consumed message
+    pub fn into_typed<T: for<'v> FromDicey<'v>>(self) -> Result<T, Error> {
+        self.reply_value().and_then(|ref view| T::from_dicey(view))
+    }
+
+    // libdicey doesn't look at the characters of a path, so a peer may send anything. Sequences that aren't UTF-8 are
+    // replaced as String::from_utf8_lossy does, while path_bytes returns the path exactly as it was sent
+    pub fn path(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(self.path_bytes())
+    }
+
+    pub fn path_bytes(&self) -> &[u8] {
         match self {
-            Message::Event(e) => e.path(),
-            Message::Exec(e) => e.path(),
-            Message::Get(e) => e.path(),
-            Message::Response(e) => e.path(),
-            Message::Set(e) => e.path(),
+            Message::Event(e) => e.0.path_bytes(),
+            Message::Exec(e) => e.0.path_bytes(),
+            Message::Get(e) => e.0.path_bytes(),
+            Message::Response(e) => e.0.path_bytes(),
+            Message::Set(e) => e.0.path_bytes(),
         }
     }
 
@@ -331,6 +502,35 @@ impl Message {
         }
     }
 
+    // the packet retains the buffer it was loaded or built from, so these are the exact bytes sent on the wire
+    pub fn raw_bytes(&self) -> &[u8] {
+        match self {
+            Message::Event(e) => e.0.rpacket.as_bytes(),
+            Message::Exec(e) => e.0.rpacket.as_bytes(),
+            Message::Get(e) => e.0.rpacket.as_bytes(),
+            Message::Response(e) => e.0.rpacket.as_bytes(),
+            Message::Set(e) => e.0.rpacket.as_bytes(),
+        }
+    }
+
+    pub fn respond(&self, value: impl ToDicey) -> Result<Message, Error> {
+        MessageBuilder::response()?
+            .seq(self.seq())
+            .path(self.path_bytes())?
+            .selector(self.selector())?
+            .value(value)?
+            .build()
+    }
+
+    pub fn respond_error(&self, code: i16, message: Option<&str>) -> Result<Message, Error> {
+        self.respond(ErrorMessage { code, message })
+    }
+
+    // owned copies of the path and selector, e.g. to look up a handler in a map keyed by them
+    pub fn route_key(&self) -> (String, SelectorBuf) {
+        (self.path().into_owned(), self.selector().to_owned())
+    }
+
     pub fn seq(&self) -> u32 {
         match self {
             Message::Event(e) => e.seq(),
@@ -351,6 +551,45 @@ impl Message {
         }
     }
 
+    // the value of a reply, or the error it carries
+    pub(crate) fn reply_value(&self) -> Result<ValueView<'_>, Error> {
+        match self.value() {
+            Some(ValueView::Error(e)) => Err(e.into_error()),
+            Some(view) => Ok(view),
+            None => Err(Error::BadMessage),
+        }
+    }
+
+    /// Like [`Message::value`], but values of types these bindings don't recognise decode to [`ValueView::Unknown`]
+    /// instead of failing, so that the rest of the value can still be read. Strings that aren't UTF-8 are handed over as
+    /// [`ValueView::Bytes`], which [`ValueView::str_lossy`] reads back as strings.
+    pub fn value_lenient(&self) -> Option<ValueView<'_>> {
+        match self {
+            Message::Event(e) => Some(e.0.value_lenient()),
+            Message::Exec(e) => Some(e.0.value_lenient()),
+            Message::Get(_) => None,
+            Message::Response(e) => Some(e.0.value_lenient()),
+            Message::Set(e) => Some(e.0.value_lenient()),
+        }
+    }
+
+    pub fn value_array_iter(&self) -> Result<ListIter<'_>, Error> {
+        match self {
+            Message::Event(e) => e.value_array_iter(),
+            Message::Exec(e) => e.value_array_iter(),
+            Message::Get(_) => Err(Error::ValueTypeMismatch),
+            Message::Response(e) => e.value_array_iter(),
+            Message::Set(e) => e.value_array_iter(),
+        }
+    }
+
+    /// Reads an array of fixed-width numbers straight into a `Vec`, without decoding each item into a [`ValueView`].
+    ///
+    /// Fails with [`Error::ValueTypeMismatch`] unless the value is an array of `T`.
+    pub fn value_primitive_array<T: Primitive>(&self) -> Result<Vec<T>, Error> {
+        self.value_array_iter()?.read_primitive_array()
+    }
+
     pub(crate) fn from_raw(cpacket: dicey_packet) -> Result<Self, Error> {
         RawPacket::from(cpacket).try_into()
     }
@@ -390,7 +629,18 @@ impl TryFrom<RawPacket> for Message {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+impl TryFrom<Packet> for Message {
+    type Error = Error;
+
+    fn try_from(packet: Packet) -> Result<Self, Self::Error> {
+        match packet {
+            Packet::Message(m) => Ok(m),
+            Packet::Bye(_) | Packet::Hello(_) => Err(Error::InvalidData),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Op {
     Event,
     Exec,
@@ -411,6 +661,7 @@ impl Op {
     }
 }
 
+#[derive(Clone)]
 pub struct Response(RawMessage);
 
 impl Response {
@@ -418,7 +669,7 @@ impl Response {
         self.0.seq()
     }
 
-    pub fn path(&self) -> &str {
+    pub fn path(&self) -> Cow<'_, str> {
         self.0.path()
     }
 
@@ -430,6 +681,10 @@ impl Response {
         self.0.value()
     }
 
+    pub fn value_array_iter(&self) -> Result<ListIter<'_>, Error> {
+        self.0.value_array_iter()
+    }
+
     pub(crate) fn into_raw(self) -> dicey_packet {
         self.0.into_raw()
     }
@@ -441,6 +696,7 @@ impl fmt::Debug for Response {
     }
 }
 
+#[derive(Clone)]
 pub struct Set(RawMessage);
 
 impl Set {
@@ -448,7 +704,7 @@ impl Set {
         self.0.seq()
     }
 
-    pub fn path(&self) -> &str {
+    pub fn path(&self) -> Cow<'_, str> {
         self.0.path()
     }
 
@@ -460,6 +716,10 @@ impl Set {
         self.0.value()
     }
 
+    pub fn value_array_iter(&self) -> Result<ListIter<'_>, Error> {
+        self.0.value_array_iter()
+    }
+
     pub(crate) fn into_raw(self) -> dicey_packet {
         self.0.into_raw()
     }
@@ -522,10 +782,12 @@ impl RawMessage {
         self.rpacket.into_raw()
     }
 
-    fn path(&self) -> &str {
-        unsafe { std::ffi::CStr::from_ptr(self.c_data.path) }
-            .to_str()
-            .expect("the path must be ASCII")
+    fn path(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(self.path_bytes())
+    }
+
+    fn path_bytes(&self) -> &[u8] {
+        unsafe { CStr::from_ptr(self.c_data.path) }.to_bytes()
     }
 
     fn selector(&self) -> Selector<'_> {
@@ -540,6 +802,15 @@ impl RawMessage {
         ValueView::try_from(self.c_data.value)
             .expect("the value must be valid, this is probably a C bug")
     }
+
+    fn value_lenient(&self) -> ValueView<'_> {
+        ValueView::decode(self.c_data.value, true)
+            .expect("the value must be valid, this is probably a C bug")
+    }
+
+    fn value_array_iter(&self) -> Result<ListIter<'_>, Error> {
+        ListIter::try_from(self.c_data.value)
+    }
 }
 
 impl TryFrom<RawPacket> for RawMessage {
@@ -560,6 +831,13 @@ impl TryFrom<RawPacket> for RawMessage {
     }
 }
 
+impl Clone for RawMessage {
+    fn clone(&self) -> Self {
+        RawMessage::try_from(self.rpacket.clone())
+            .expect("a copy of a valid message must be a valid message")
+    }
+}
+
 // RawMessage is send and sync because it's literally a pointer to a byte array plus a C struct containing some
 // pointers into the same byte array
 unsafe impl Send for RawMessage {}
@@ -583,6 +861,10 @@ impl RawPacket {
         }
     }
 
+    fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.content.payload as *const u8, self.content.nbytes) }
+    }
+
     fn into_raw(self) -> dicey_packet {
         // return the C packet and present self from being dropped
         ManuallyDrop::new(self).content
@@ -602,6 +884,13 @@ impl RawPacket {
     }
 }
 
+impl Clone for RawPacket {
+    fn clone(&self) -> Self {
+        // reloading the payload is exactly what dumping and loading a packet boils down to
+        RawPacket::load(self.as_bytes()).expect("failed to copy packet (out of memory?)")
+    }
+}
+
 impl Drop for RawPacket {
     fn drop(&mut self) {
         unsafe {
@@ -622,8 +911,8 @@ impl From<dicey_packet> for RawPacket {
 unsafe impl Send for RawPacket {}
 unsafe impl Sync for RawPacket {}
 
-fn map_io_error(err: io::Error) -> Error {
-    match err.kind() {
+fn map_io_error(err: io::Error) -> DiceyError {
+    let kind = match err.kind() {
         io::ErrorKind::NotFound => Error::FileNotFound,
         io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => Error::InvalidData,
         io::ErrorKind::ConnectionRefused => Error::ConnectionRefused,
@@ -633,5 +922,112 @@ fn map_io_error(err: io::Error) -> Error {
         io::ErrorKind::BrokenPipe => Error::BrokenPipe,
 
         _ => Error::UnknownUVError, // fine for now
+    };
+
+    DiceyError::with_source(kind, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a Set message for `/foo`, `a.Trait:Prop` carrying `value`; shared with the tests of the other core modules
+    pub(super) fn set_value(value: impl ToDicey) -> Message {
+        MessageBuilder::set()
+            .unwrap()
+            .path("/foo")
+            .unwrap()
+            .selector(("a.Trait", "Prop"))
+            .unwrap()
+            .value(value)
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_packet_message() {
+        let packet =
+            Packet::message(Op::Event, 7, "/foo", ("a.Trait", "Signal"), Some(42u32)).unwrap();
+        let reloaded = Packet::load(packet.raw_bytes()).unwrap();
+
+        let msg = reloaded.as_message().unwrap();
+        assert_eq!(msg.op(), Op::Event);
+        assert_eq!(msg.seq(), 7);
+        assert_eq!(msg.path(), "/foo");
+        assert_eq!(msg.selector(), Selector::from(("a.Trait", "Signal")));
+        assert_eq!(msg.value(), Some(ValueView::UInt32(42)));
+
+        let get = Packet::message(Op::Get, 2, "/foo", ("a.Trait", "Prop"), None::<()>);
+        assert!(get.unwrap().as_message().unwrap().value().is_none());
+
+        let exec = Packet::message(Op::Exec, 2, "/foo", ("a.Trait", "Op"), None::<()>);
+        assert!(matches!(exec, Err(Error::InvalidData)));
+
+        let get = Packet::message(Op::Get, 2, "/foo", ("a.Trait", "Prop"), Some(()));
+        assert!(matches!(get, Err(Error::InvalidData)));
+    }
+
+    #[test]
+    fn test_load_path() {
+        let path = "/dicey/test/missing.bin";
+
+        assert_eq!(Packet::load_path(path).err(), Some(Error::FileNotFound));
+
+        let err = Packet::load_path_with_source(path).unwrap_err();
+        assert_eq!(err.kind(), Error::FileNotFound);
+        assert_eq!(
+            std::error::Error::source(&err)
+                .and_then(|source| source.downcast_ref::<io::Error>())
+                .map(io::Error::kind),
+            Some(io::ErrorKind::NotFound)
+        );
+    }
+
+    #[test]
+    fn test_into_typed() {
+        assert_eq!(set_value("hi").into_typed::<String>(), Ok("hi".to_string()));
+        assert_eq!(
+            set_value(ErrorMessage::new(Error::PathNotFound.code() as i16, None))
+                .into_typed::<String>(),
+            Err(Error::PathNotFound)
+        );
+
+        // codes of the server's own have no library error to map to
+        assert_eq!(
+            set_value(ErrorMessage::new(1234, "custom")).into_typed::<String>(),
+            Err(Error::InvalidData)
+        );
+        assert_eq!(
+            set_value(42u32).into_typed::<String>(),
+            Err(Error::ValueTypeMismatch)
+        );
+
+        let get = MessageBuilder::get()
+            .unwrap()
+            .path("/foo")
+            .unwrap()
+            .selector(("a.Trait", "Prop"))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(get.into_typed::<()>(), Err(Error::BadMessage));
+    }
+
+    #[test]
+    fn test_route_key() {
+        let handlers = std::collections::HashMap::from([(
+            (
+                "/foo".to_string(),
+                Selector::from(("a.Trait", "Prop")).to_owned(),
+            ),
+            "prop",
+        )]);
+
+        let (path, selector) = set_value(()).route_key();
+        assert_eq!(path, "/foo");
+        assert_eq!(selector.as_selector(), Selector::from(("a.Trait", "Prop")));
+
+        assert_eq!(handlers.get(&(path, selector)), Some(&"prop"));
     }
 }
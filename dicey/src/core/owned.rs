@@ -0,0 +1,472 @@
+/*
+ * Copyright (c) 2014-2024 Zuru Tech HK Limited, All rights reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use uuid::Uuid;
+
+use super::{
+    Message, MessageBuilder, Op,
+    builder::ToDicey,
+    errors::Error,
+    value::{Byte, ErrorMessage, FromDicey, Path, PathBuf, SelectorBuf, Type, ValueView},
+};
+
+// an owned counterpart of ValueView, for values that must outlive the packet they were received with
+#[derive(Clone, Debug, PartialEq)]
+pub enum OwnedValue {
+    Unit,
+
+    Bool(bool),
+    Byte(Byte),
+
+    Float(f64),
+
+    Int16(i16),
+    Int32(i32),
+    Int64(i64),
+    UInt16(u16),
+    UInt32(u32),
+    UInt64(u64),
+
+    Array {
+        element_kind: Type,
+        items: Vec<OwnedValue>,
+    },
+    Tuple(Vec<OwnedValue>),
+    Pair(Box<OwnedValue>, Box<OwnedValue>),
+
+    Bytes(Vec<u8>),
+    String(String),
+
+    Uuid(Uuid),
+
+    Path(PathBuf),
+    Selector(SelectorBuf),
+
+    Unknown {
+        type_byte: u8,
+    },
+
+    Error {
+        code: i16,
+        message: Option<String>,
+    },
+}
+
+impl OwnedValue {
+    pub fn as_view(&self) -> ValueView<'_> {
+        match self {
+            OwnedValue::Unit => ValueView::Unit,
+            OwnedValue::Bool(b) => ValueView::Bool(*b),
+            OwnedValue::Byte(b) => ValueView::Byte(*b),
+            OwnedValue::Float(f) => ValueView::Float(*f),
+            OwnedValue::Int16(i) => ValueView::Int16(*i),
+            OwnedValue::Int32(i) => ValueView::Int32(*i),
+            OwnedValue::Int64(i) => ValueView::Int64(*i),
+            OwnedValue::UInt16(u) => ValueView::UInt16(*u),
+            OwnedValue::UInt32(u) => ValueView::UInt32(*u),
+            OwnedValue::UInt64(u) => ValueView::UInt64(*u),
+            OwnedValue::Array {
+                element_kind,
+                items,
+            } => ValueView::Array {
+                element_kind: *element_kind,
+                items: items.iter().map(OwnedValue::as_view).collect(),
+            },
+            OwnedValue::Tuple(items) => {
+                ValueView::Tuple(items.iter().map(OwnedValue::as_view).collect())
+            }
+            OwnedValue::Pair(first, second) => {
+                ValueView::Pair(Box::new(first.as_view()), Box::new(second.as_view()))
+            }
+            OwnedValue::Bytes(bytes) => ValueView::Bytes(bytes),
+            OwnedValue::String(s) => ValueView::String(s),
+            OwnedValue::Uuid(uuid) => ValueView::Uuid(*uuid),
+            OwnedValue::Path(path) => ValueView::Path(path),
+            OwnedValue::Selector(sel) => ValueView::Selector(sel.as_selector()),
+            OwnedValue::Error { code, message } => ValueView::Error(ErrorMessage {
+                code: *code,
+                message: message.as_deref(),
+            }),
+            OwnedValue::Unknown { type_byte } => ValueView::Unknown {
+                type_byte: *type_byte,
+            },
+        }
+    }
+
+    pub const fn kind(&self) -> Type {
+        match self {
+            OwnedValue::Unit => Type::Unit,
+            OwnedValue::Bool(_) => Type::Bool,
+            OwnedValue::Byte(_) => Type::Byte,
+            OwnedValue::Float(_) => Type::Float,
+            OwnedValue::Int16(_) => Type::Int16,
+            OwnedValue::Int32(_) => Type::Int32,
+            OwnedValue::Int64(_) => Type::Int64,
+            OwnedValue::UInt16(_) => Type::UInt16,
+            OwnedValue::UInt32(_) => Type::UInt32,
+            OwnedValue::UInt64(_) => Type::UInt64,
+            OwnedValue::Array { .. } => Type::Array,
+            OwnedValue::Tuple(_) => Type::Tuple,
+            OwnedValue::Pair(_, _) => Type::Pair,
+            OwnedValue::Bytes(_) => Type::Bytes,
+            OwnedValue::String(_) => Type::String,
+            OwnedValue::Uuid(_) => Type::Uuid,
+            OwnedValue::Path(_) => Type::Path,
+            OwnedValue::Selector(_) => Type::Selector,
+            OwnedValue::Error { .. } => Type::Error,
+            OwnedValue::Unknown { type_byte } => Type::Unknown(*type_byte),
+        }
+    }
+}
+
+impl From<&ValueView<'_>> for OwnedValue {
+    fn from(value: &ValueView<'_>) -> Self {
+        match value {
+            ValueView::Unit => OwnedValue::Unit,
+            ValueView::Bool(b) => OwnedValue::Bool(*b),
+            ValueView::Byte(b) => OwnedValue::Byte(*b),
+            ValueView::Float(f) => OwnedValue::Float(*f),
+            ValueView::Int16(i) => OwnedValue::Int16(*i),
+            ValueView::Int32(i) => OwnedValue::Int32(*i),
+            ValueView::Int64(i) => OwnedValue::Int64(*i),
+            ValueView::UInt16(u) => OwnedValue::UInt16(*u),
+            ValueView::UInt32(u) => OwnedValue::UInt32(*u),
+            ValueView::UInt64(u) => OwnedValue::UInt64(*u),
+            ValueView::Array {
+                element_kind,
+                items,
+            } => OwnedValue::Array {
+                element_kind: *element_kind,
+                items: items.iter().map(OwnedValue::from).collect(),
+            },
+            ValueView::Tuple(items) => {
+                OwnedValue::Tuple(items.iter().map(OwnedValue::from).collect())
+            }
+            ValueView::Pair(first, second) => OwnedValue::Pair(
+                Box::new(OwnedValue::from(&**first)),
+                Box::new(OwnedValue::from(&**second)),
+            ),
+            ValueView::Bytes(bytes) => OwnedValue::Bytes(bytes.to_vec()),
+            ValueView::String(s) => OwnedValue::String((*s).to_owned()),
+            ValueView::Uuid(uuid) => OwnedValue::Uuid(*uuid),
+            ValueView::Path(path) => OwnedValue::Path(Path::to_owned(path)),
+            ValueView::Selector(sel) => OwnedValue::Selector(sel.to_owned()),
+            ValueView::Error(err) => OwnedValue::Error {
+                code: err.code,
+                message: err.message.map(str::to_owned),
+            },
+            ValueView::Unknown { type_byte } => OwnedValue::Unknown {
+                type_byte: *type_byte,
+            },
+        }
+    }
+}
+
+impl From<ValueView<'_>> for OwnedValue {
+    fn from(value: ValueView<'_>) -> Self {
+        OwnedValue::from(&value)
+    }
+}
+
+impl FromDicey<'_> for OwnedValue {
+    fn from_dicey(value: &ValueView<'_>) -> Result<Self, Error> {
+        Ok(value.into())
+    }
+}
+
+// an owned counterpart of Message, with its contents decoded, for when they must be inspected or changed freely
+#[derive(Clone, Debug, PartialEq)]
+pub struct OwnedMessage {
+    pub op: Op,
+    pub seq: u32,
+    // paths that aren't UTF-8 have their invalid sequences replaced
+    pub path: String,
+    pub selector: SelectorBuf,
+
+    // None for Get requests, which carry no value
+    pub value: Option<OwnedValue>,
+}
+
+impl From<&Message> for OwnedMessage {
+    fn from(msg: &Message) -> Self {
+        OwnedMessage {
+            op: msg.op(),
+            seq: msg.seq(),
+            path: msg.path().into_owned(),
+            selector: msg.selector().to_owned(),
+            value: msg.value().map(OwnedValue::from),
+        }
+    }
+}
+
+impl TryFrom<&OwnedMessage> for Message {
+    type Error = Error;
+
+    fn try_from(msg: &OwnedMessage) -> Result<Self, Error> {
+        let builder = MessageBuilder::new(msg.op)?
+            .seq(msg.seq)
+            .path(msg.path.as_str())?
+            .selector(msg.selector.as_selector())?;
+
+        match &msg.value {
+            Some(value) => builder.value_with(|builder| value.to_dicey(builder))?,
+            None => builder,
+        }
+        .build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::borrow::Cow;
+
+    use crate::{Packet, Tuple2};
+
+    use proptest::{collection::vec, option, prelude::*};
+
+    // NaNs never compare equal, even to themselves
+    fn float() -> impl Strategy<Value = f64> {
+        use prop::num::f64::*;
+
+        POSITIVE | NEGATIVE | NORMAL | SUBNORMAL | ZERO | INFINITE
+    }
+
+    // dicey strings are C strings, so they can't hold NULs
+    fn text() -> impl Strategy<Value = String> {
+        "\\PC{0,12}"
+    }
+
+    impl Arbitrary for OwnedValue {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        // Unknown is only produced by lenient decoding, and doesn't survive a strict reload
+        fn arbitrary_with(_: ()) -> Self::Strategy {
+            let leaf = prop_oneof![
+                Just(OwnedValue::Unit),
+                any::<bool>().prop_map(OwnedValue::Bool),
+                any::<u8>().prop_map(|b| OwnedValue::Byte(Byte(b))),
+                float().prop_map(OwnedValue::Float),
+                any::<i16>().prop_map(OwnedValue::Int16),
+                any::<i32>().prop_map(OwnedValue::Int32),
+                any::<i64>().prop_map(OwnedValue::Int64),
+                any::<u16>().prop_map(OwnedValue::UInt16),
+                any::<u32>().prop_map(OwnedValue::UInt32),
+                any::<u64>().prop_map(OwnedValue::UInt64),
+                // empty bytes don't survive a reload, see test_empty_bytes
+                vec(any::<u8>(), 1..16).prop_map(OwnedValue::Bytes),
+                text().prop_map(OwnedValue::String),
+                any::<u128>().prop_map(|u| OwnedValue::Uuid(Uuid::from_u128(u))),
+                text().prop_map(|t| OwnedValue::Path(PathBuf::from(format!("/{t}")))),
+                (text(), text()).prop_map(|(trait_name, elem)| {
+                    OwnedValue::Selector(SelectorBuf {
+                        trait_name: trait_name.into(),
+                        elem: elem.into(),
+                    })
+                }),
+                // empty messages reload as missing ones, see test_empty_error_message
+                (any::<i16>(), option::of("\\PC{1,12}"))
+                    .prop_map(|(code, message)| OwnedValue::Error { code, message }),
+            ];
+
+            leaf.prop_recursive(4, 64, 5, |inner| {
+                prop_oneof![
+                    // arrays take the kind of their first item, and keep up to `len` of the items of that kind
+                    (vec(inner.clone(), 1..6), 0..5usize).prop_filter_map(
+                        "arrays of units reload as empty, see test_unit_array",
+                        |(items, len)| {
+                            let element_kind = items[0].kind();
+
+                            (element_kind != Type::Unit).then(|| OwnedValue::Array {
+                                element_kind,
+                                items: items
+                                    .into_iter()
+                                    .filter(|item| item.kind() == element_kind)
+                                    .take(len)
+                                    .collect(),
+                            })
+                        }
+                    ),
+                    vec(inner.clone(), 0..5).prop_map(OwnedValue::Tuple),
+                    (inner.clone(), inner).prop_map(|(first, second)| OwnedValue::Pair(
+                        Box::new(first),
+                        Box::new(second)
+                    )),
+                ]
+            })
+            .boxed()
+        }
+    }
+
+    fn reload(value: &impl ToDicey) -> Message {
+        let msg = MessageBuilder::set()
+            .unwrap()
+            .path("/foo")
+            .unwrap()
+            .selector(("a.Trait", "Prop"))
+            .unwrap()
+            .value_with(|builder| value.to_dicey(builder))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        Message::try_from(Packet::load(msg.raw_bytes()).unwrap()).unwrap()
+    }
+
+    fn check_typed<T>(value: T) -> Result<(), TestCaseError>
+    where
+        T: ToDicey + for<'a> FromDicey<'a> + PartialEq + std::fmt::Debug,
+    {
+        prop_assert_eq!(reload(&value).value().unwrap().extract::<T>(), Ok(value));
+
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn test_value_roundtrip(value in any::<OwnedValue>()) {
+            let reloaded = reload(&value);
+
+            prop_assert_eq!(OwnedValue::from_dicey(&reloaded.value().unwrap()), Ok(value));
+        }
+
+        #[test]
+        fn test_typed_roundtrip(
+            f in float(),
+            b in any::<u8>(),
+            s in text(),
+            t in (any::<i16>(), text()),
+            v in vec((any::<u32>(), text()), 0..8),
+        ) {
+            check_typed(f as f32)?;
+            check_typed(Byte(b))?;
+            check_typed(s)?;
+            check_typed(Tuple2(t.0, t.1))?;
+            check_typed(v)?;
+        }
+    }
+
+    #[test]
+    fn test_cow_roundtrip() {
+        let msg = reload(&Cow::Borrowed("borrowed"));
+        assert_eq!(
+            msg.value().unwrap().extract::<Cow<str>>(),
+            Ok(Cow::Borrowed("borrowed"))
+        );
+
+        let msg = reload(&Cow::<[u8]>::Owned(vec![1, 2, 3]));
+        assert_eq!(
+            msg.value().unwrap().extract::<Cow<[u8]>>(),
+            Ok(Cow::Borrowed(&[1u8, 2, 3][..]))
+        );
+    }
+
+    // unit items take no space on the wire and libdicey walks arrays by byte length, so an array of units always
+    // reloads as empty
+    #[test]
+    fn test_unit_array() {
+        let msg = reload(&vec![(), ()]);
+
+        assert_eq!(
+            msg.value().unwrap(),
+            ValueView::Array {
+                element_kind: Type::Unit,
+                items: vec![],
+            }
+        );
+    }
+
+    // empty bytes are encoded and decoded like any other, even if other libdicey peers would refuse them
+    #[test]
+    fn test_empty_bytes() {
+        let msg = MessageBuilder::set()
+            .unwrap()
+            .path("/foo")
+            .unwrap()
+            .selector(("a.Trait", "Prop"))
+            .unwrap()
+            .value(Vec::<u8>::new())
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(msg.value(), Some(ValueView::Bytes(&[])));
+        assert_eq!(
+            msg.value().unwrap().extract::<Vec<u8>>().unwrap(),
+            Vec::<u8>::new()
+        );
+
+        // libdicey's packet loader wrongly refuses empty bytes, so they only round-trip within the built message
+        assert!(matches!(
+            Packet::load(msg.raw_bytes()),
+            Err(Error::BadMessage)
+        ));
+    }
+
+    // the wire can't tell an empty error message from a missing one
+    #[test]
+    fn test_empty_error_message() {
+        let msg = reload(&ErrorMessage {
+            code: 1,
+            message: Some(""),
+        });
+
+        assert_eq!(
+            msg.value().unwrap(),
+            ValueView::Error(ErrorMessage {
+                code: 1,
+                message: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_owned_message_roundtrip() {
+        let msg = MessageBuilder::exec()
+            .unwrap()
+            .seq(4)
+            .path("/foo")
+            .unwrap()
+            .selector(("a.Trait", "Op"))
+            .unwrap()
+            .value((1u32, vec!["a", "b"]))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let owned = msg.clone().into_owned();
+        assert_eq!(owned.op, Op::Exec);
+        assert_eq!(owned.path, "/foo");
+        assert_eq!(owned.selector.as_selector(), msg.selector());
+
+        let rebuilt = Message::try_from(&owned).unwrap();
+        assert_eq!(rebuilt.raw_bytes(), msg.raw_bytes());
+
+        let get = MessageBuilder::get()
+            .unwrap()
+            .path("/foo")
+            .unwrap()
+            .selector(("a.Trait", "Prop"))
+            .unwrap()
+            .build()
+            .unwrap()
+            .into_owned();
+        assert_eq!(get.value, None);
+        assert!(Message::try_from(&get).is_ok());
+    }
+}
@@ -0,0 +1,376 @@
+/*
+ * Copyright (c) 2014-2024 Zuru Tech HK Limited, All rights reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{error, fmt, str::FromStr};
+
+use super::{
+    errors::Error,
+    value::{Type, ValueView},
+};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Signature {
+    Unit,
+    Bool,
+    Byte,
+    Float,
+    Int16,
+    Int32,
+    Int64,
+    UInt16,
+    UInt32,
+    UInt64,
+
+    Array(Box<Signature>),
+    Tuple(Vec<Signature>),
+    Pair(Box<Signature>, Box<Signature>),
+
+    Bytes,
+    String,
+    Uuid,
+    Path,
+    Selector,
+    Error,
+
+    Variant,
+
+    Operation {
+        input: Box<Signature>,
+        output: Box<Signature>,
+    },
+}
+
+impl Signature {
+    pub fn parse(sig: &str) -> Result<Self, Error> {
+        let (sig, output) = match sig.split_once("->") {
+            Some((input, output)) => (input.trim(), Some(output.trim())),
+            None => (sig, None),
+        };
+
+        let input = parse_value_sig(sig)?;
+
+        match output {
+            Some(output) => Ok(Signature::Operation {
+                input: Box::new(input),
+                output: Box::new(parse_value_sig(output)?),
+            }),
+            None => Ok(input),
+        }
+    }
+
+    // returns the kind of value this signature describes, or None for variants and operations
+    pub const fn kind(&self) -> Option<Type> {
+        match self {
+            Signature::Unit => Some(Type::Unit),
+            Signature::Bool => Some(Type::Bool),
+            Signature::Byte => Some(Type::Byte),
+            Signature::Float => Some(Type::Float),
+            Signature::Int16 => Some(Type::Int16),
+            Signature::Int32 => Some(Type::Int32),
+            Signature::Int64 => Some(Type::Int64),
+            Signature::UInt16 => Some(Type::UInt16),
+            Signature::UInt32 => Some(Type::UInt32),
+            Signature::UInt64 => Some(Type::UInt64),
+            Signature::Array(_) => Some(Type::Array),
+            Signature::Tuple(_) => Some(Type::Tuple),
+            Signature::Pair(_, _) => Some(Type::Pair),
+            Signature::Bytes => Some(Type::Bytes),
+            Signature::String => Some(Type::String),
+            Signature::Uuid => Some(Type::Uuid),
+            Signature::Path => Some(Type::Path),
+            Signature::Selector => Some(Type::Selector),
+            Signature::Error => Some(Type::Error),
+            Signature::Variant | Signature::Operation { .. } => None,
+        }
+    }
+
+    /// Checks whether `value` has the shape described by this signature.
+    ///
+    /// Operation signatures are matched against their output, i.e. the value a server responds with. In that case
+    /// error values always match, given that any operation may fail.
+    pub fn matches(&self, value: &ValueView<'_>) -> bool {
+        self.matches_detailed(value).is_ok()
+    }
+
+    pub fn matches_detailed(&self, value: &ValueView<'_>) -> Result<(), SignatureMismatch> {
+        match (self, value) {
+            (Signature::Variant, _) => Ok(()),
+
+            (Signature::Operation { .. }, ValueView::Error(_)) => Ok(()),
+            (Signature::Operation { output, .. }, _) => output.matches_detailed(value),
+
+            (
+                Signature::Array(elem_sig),
+                ValueView::Array {
+                    element_kind,
+                    items,
+                },
+            ) => {
+                if let Some(kind) = elem_sig.kind()
+                    && kind != *element_kind
+                {
+                    return Err(SignatureMismatch::ElementKind {
+                        expected: (**elem_sig).clone(),
+                        found: *element_kind,
+                    });
+                }
+
+                items
+                    .iter()
+                    .try_for_each(|item| elem_sig.matches_detailed(item))
+            }
+
+            (Signature::Tuple(sigs), ValueView::Tuple(items)) => {
+                if sigs.len() != items.len() {
+                    return Err(SignatureMismatch::Arity {
+                        expected: sigs.len(),
+                        found: items.len(),
+                    });
+                }
+
+                sigs.iter()
+                    .zip(items)
+                    .try_for_each(|(sig, item)| sig.matches_detailed(item))
+            }
+
+            (Signature::Pair(first_sig, second_sig), ValueView::Pair(first, second)) => {
+                first_sig.matches_detailed(first)?;
+                second_sig.matches_detailed(second)
+            }
+
+            _ if self.kind() == Some(value.kind()) => Ok(()),
+
+            _ => Err(SignatureMismatch::Kind {
+                expected: self.clone(),
+                found: value.kind(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Signature::Unit => f.write_str("$"),
+            Signature::Bool => f.write_str("b"),
+            Signature::Byte => f.write_str("c"),
+            Signature::Float => f.write_str("f"),
+            Signature::Int16 => f.write_str("n"),
+            Signature::Int32 => f.write_str("i"),
+            Signature::Int64 => f.write_str("x"),
+            Signature::UInt16 => f.write_str("q"),
+            Signature::UInt32 => f.write_str("u"),
+            Signature::UInt64 => f.write_str("t"),
+            Signature::Array(elem) => write!(f, "[{elem}]"),
+            Signature::Tuple(elems) => {
+                f.write_str("(")?;
+
+                for elem in elems {
+                    elem.fmt(f)?;
+                }
+
+                f.write_str(")")
+            }
+            Signature::Pair(first, second) => write!(f, "{{{first}{second}}}"),
+            Signature::Bytes => f.write_str("y"),
+            Signature::String => f.write_str("s"),
+            Signature::Uuid => f.write_str("#"),
+            Signature::Path => f.write_str("@"),
+            Signature::Selector => f.write_str("%"),
+            Signature::Error => f.write_str("e"),
+            Signature::Variant => f.write_str("v"),
+            Signature::Operation { input, output } => write!(f, "{input} -> {output}"),
+        }
+    }
+}
+
+impl FromStr for Signature {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl TryFrom<&str> for Signature {
+    type Error = Error;
+
+    fn try_from(s: &str) -> Result<Self, Error> {
+        Self::parse(s)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SignatureMismatch {
+    Kind { expected: Signature, found: Type },
+    ElementKind { expected: Signature, found: Type },
+    Arity { expected: usize, found: usize },
+}
+
+impl fmt::Display for SignatureMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureMismatch::Kind { expected, found } => {
+                write!(f, "expected a value of type `{expected}`, found {found:?}")
+            }
+            SignatureMismatch::ElementKind { expected, found } => {
+                write!(
+                    f,
+                    "expected an array of `{expected}`, found an array of {found:?}"
+                )
+            }
+            SignatureMismatch::Arity { expected, found } => {
+                write!(f, "expected a tuple of {expected} items, found {found}")
+            }
+        }
+    }
+}
+
+impl error::Error for SignatureMismatch {}
+
+fn parse_value_sig(sig: &str) -> Result<Signature, Error> {
+    let mut bytes = sig.as_bytes();
+
+    let ret = parse_value(&mut bytes)?;
+
+    if bytes.is_empty() {
+        Ok(ret)
+    } else {
+        Err(Error::MalformedSignature)
+    }
+}
+
+fn parse_value(bytes: &mut &[u8]) -> Result<Signature, Error> {
+    let (&first, rest) = bytes.split_first().ok_or(Error::MalformedSignature)?;
+
+    *bytes = rest;
+
+    Ok(match first {
+        b'$' => Signature::Unit,
+        b'b' => Signature::Bool,
+        b'c' => Signature::Byte,
+        b'f' => Signature::Float,
+        b'n' => Signature::Int16,
+        b'i' => Signature::Int32,
+        b'x' => Signature::Int64,
+        b'q' => Signature::UInt16,
+        b'u' => Signature::UInt32,
+        b't' => Signature::UInt64,
+        b'y' => Signature::Bytes,
+        b's' => Signature::String,
+        b'#' => Signature::Uuid,
+        b'@' => Signature::Path,
+        b'%' => Signature::Selector,
+        b'e' => Signature::Error,
+        b'v' => Signature::Variant,
+
+        b'[' => {
+            let elem = parse_value(bytes)?;
+
+            expect(bytes, b']')?;
+
+            Signature::Array(Box::new(elem))
+        }
+
+        b'(' => {
+            let mut elems = vec![parse_value(bytes)?];
+
+            while bytes.first().is_some_and(|&b| b != b')') {
+                elems.push(parse_value(bytes)?);
+            }
+
+            expect(bytes, b')')?;
+
+            Signature::Tuple(elems)
+        }
+
+        b'{' => {
+            let first = parse_value(bytes)?;
+            let second = parse_value(bytes)?;
+
+            expect(bytes, b'}')?;
+
+            Signature::Pair(Box::new(first), Box::new(second))
+        }
+
+        _ => return Err(Error::MalformedSignature),
+    })
+}
+
+fn expect(bytes: &mut &[u8], close: u8) -> Result<(), Error> {
+    match bytes.split_first() {
+        Some((&b, rest)) if b == close => {
+            *bytes = rest;
+
+            Ok(())
+        }
+        _ => Err(Error::MalformedSignature),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_roundtrip() {
+        for sig in ["$", "[s]", "(i@b)", "{@%}", "[{s(cb)}]", "[(@%)] -> $"] {
+            assert_eq!(Signature::parse(sig).unwrap().to_string(), sig);
+        }
+
+        assert_eq!(
+            Signature::parse("i->s").unwrap(),
+            Signature::parse("i   ->   s").unwrap(),
+        );
+
+        for sig in ["", "[s", "()", "{s}", "ss", "z"] {
+            assert_eq!(Signature::parse(sig), Err(Error::MalformedSignature));
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        let value = ValueView::Tuple(vec![
+            ValueView::Int32(42),
+            ValueView::Array {
+                element_kind: Type::String,
+                items: vec![ValueView::String("hello")],
+            },
+        ]);
+
+        assert!(Signature::parse("(i[s])").unwrap().matches(&value));
+        assert!(Signature::parse("(v[v])").unwrap().matches(&value));
+        assert!(Signature::parse("$ -> (i[s])").unwrap().matches(&value));
+
+        assert_eq!(
+            Signature::parse("(i[b])").unwrap().matches_detailed(&value),
+            Err(SignatureMismatch::ElementKind {
+                expected: Signature::Bool,
+                found: Type::String,
+            })
+        );
+
+        assert_eq!(
+            Signature::parse("(i[s]b)")
+                .unwrap()
+                .matches_detailed(&value),
+            Err(SignatureMismatch::Arity {
+                expected: 3,
+                found: 2,
+            })
+        );
+    }
+}
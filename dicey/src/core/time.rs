@@ -0,0 +1,133 @@
+/*
+ * Copyright (c) 2014-2024 Zuru Tech HK Limited, All rights reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Timestamps are sent as UInt64 nanoseconds since the Unix epoch, 1970-01-01T00:00:00Z, not counting leap seconds, which
+// is the same scale SystemTime uses. That covers every instant from the epoch up to some time in the year 2554; anything
+// before or after can't be encoded, and fails with Error::Overflow both ways.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{
+    builder::{ToDicey, ValueBuilder},
+    errors::Error,
+    value::{FromDicey, Type, ValueView},
+};
+
+fn to_epoch_nanos(time: SystemTime) -> Result<u64, Error> {
+    let since_epoch = time
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| Error::Overflow)?;
+
+    u64::try_from(since_epoch.as_nanos()).map_err(|_| Error::Overflow)
+}
+
+fn from_epoch_nanos(nanos: u64) -> Result<SystemTime, Error> {
+    UNIX_EPOCH
+        .checked_add(Duration::from_nanos(nanos))
+        .ok_or(Error::Overflow)
+}
+
+impl ToDicey for SystemTime {
+    const TYPE_KIND: Type = Type::UInt64;
+
+    fn to_dicey(&self, builder: &mut ValueBuilder) -> Result<(), Error> {
+        to_epoch_nanos(*self)?.to_dicey(builder)
+    }
+}
+
+impl FromDicey<'_> for SystemTime {
+    fn from_dicey(value: &ValueView<'_>) -> Result<Self, Error> {
+        from_epoch_nanos(u64::from_dicey(value)?)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl ToDicey for chrono::DateTime<chrono::Utc> {
+    const TYPE_KIND: Type = Type::UInt64;
+
+    fn to_dicey(&self, builder: &mut ValueBuilder) -> Result<(), Error> {
+        SystemTime::from(*self).to_dicey(builder)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl FromDicey<'_> for chrono::DateTime<chrono::Utc> {
+    fn from_dicey(value: &ValueView<'_>) -> Result<Self, Error> {
+        SystemTime::from_dicey(value).map(Self::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::MessageBuilder;
+
+    fn reload<T: ToDicey + for<'a> FromDicey<'a>>(value: T) -> Result<T, Error> {
+        let msg = MessageBuilder::set()
+            .unwrap()
+            .path("/foo")
+            .unwrap()
+            .selector(("a.Trait", "Stamp"))
+            .unwrap()
+            .value(value)?
+            .build()
+            .unwrap();
+
+        msg.value().unwrap().extract()
+    }
+
+    #[test]
+    fn test_system_time() {
+        let time = UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789);
+
+        assert_eq!(reload(time), Ok(time));
+        assert_eq!(reload(UNIX_EPOCH), Ok(UNIX_EPOCH));
+
+        assert_eq!(
+            ValueView::UInt64(1_700_000_000_123_456_789).extract::<SystemTime>(),
+            Ok(time)
+        );
+        assert_eq!(
+            ValueView::Int64(0).extract::<SystemTime>(),
+            Err(Error::ValueTypeMismatch)
+        );
+
+        // before the epoch, and past what 64 bits of nanoseconds can hold
+        assert_eq!(
+            reload(UNIX_EPOCH - Duration::from_secs(1)),
+            Err(Error::Overflow)
+        );
+        assert_eq!(
+            reload(UNIX_EPOCH + Duration::from_nanos(u64::MAX) + Duration::from_nanos(1)),
+            Err(Error::Overflow)
+        );
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_chrono() {
+        use chrono::{DateTime, Utc};
+
+        let time = DateTime::<Utc>::from(UNIX_EPOCH + Duration::new(1_700_000_000, 42));
+
+        assert_eq!(reload(time), Ok(time));
+        assert_eq!(
+            ValueView::UInt64(1_700_000_000_000_000_042).extract::<DateTime<Utc>>(),
+            Ok(time)
+        );
+    }
+}
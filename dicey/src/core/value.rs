@@ -15,32 +15,36 @@
  */
 
 use std::{
-    borrow::Borrow,
+    borrow::{Borrow, Cow},
     ffi::{CStr, CString},
+    fmt, io,
+    iter::FusedIterator,
+    marker::PhantomData,
     mem,
     ops::Deref,
     ptr, slice,
 };
 
 use dicey_sys::{
-    dicey_errmsg, dicey_iterator_has_next, dicey_iterator_next, dicey_list, dicey_list_iter,
-    dicey_list_type, dicey_pair, dicey_selector, dicey_type, dicey_type_DICEY_TYPE_ARRAY,
-    dicey_type_DICEY_TYPE_BOOL, dicey_type_DICEY_TYPE_BYTE, dicey_type_DICEY_TYPE_BYTES,
-    dicey_type_DICEY_TYPE_ERROR, dicey_type_DICEY_TYPE_FLOAT, dicey_type_DICEY_TYPE_INT16,
-    dicey_type_DICEY_TYPE_INT32, dicey_type_DICEY_TYPE_INT64, dicey_type_DICEY_TYPE_PAIR,
-    dicey_type_DICEY_TYPE_PATH, dicey_type_DICEY_TYPE_SELECTOR, dicey_type_DICEY_TYPE_STR,
-    dicey_type_DICEY_TYPE_TUPLE, dicey_type_DICEY_TYPE_UINT16, dicey_type_DICEY_TYPE_UINT32,
-    dicey_type_DICEY_TYPE_UINT64, dicey_type_DICEY_TYPE_UNIT, dicey_type_DICEY_TYPE_UUID,
-    dicey_value, dicey_value_get_array, dicey_value_get_bool, dicey_value_get_byte,
-    dicey_value_get_bytes, dicey_value_get_error, dicey_value_get_float, dicey_value_get_i16,
-    dicey_value_get_i32, dicey_value_get_i64, dicey_value_get_pair, dicey_value_get_path,
-    dicey_value_get_selector, dicey_value_get_str, dicey_value_get_tuple, dicey_value_get_type,
-    dicey_value_get_u16, dicey_value_get_u32, dicey_value_get_u64, dicey_value_get_uuid,
+    dicey_errmsg, dicey_iterator, dicey_iterator_has_next, dicey_iterator_next, dicey_list,
+    dicey_list_iter, dicey_list_type, dicey_pair, dicey_selector, dicey_type,
+    dicey_type_DICEY_TYPE_ARRAY, dicey_type_DICEY_TYPE_BOOL, dicey_type_DICEY_TYPE_BYTE,
+    dicey_type_DICEY_TYPE_BYTES, dicey_type_DICEY_TYPE_ERROR, dicey_type_DICEY_TYPE_FLOAT,
+    dicey_type_DICEY_TYPE_INT16, dicey_type_DICEY_TYPE_INT32, dicey_type_DICEY_TYPE_INT64,
+    dicey_type_DICEY_TYPE_INVALID, dicey_type_DICEY_TYPE_PAIR, dicey_type_DICEY_TYPE_PATH,
+    dicey_type_DICEY_TYPE_SELECTOR, dicey_type_DICEY_TYPE_STR, dicey_type_DICEY_TYPE_TUPLE,
+    dicey_type_DICEY_TYPE_UINT16, dicey_type_DICEY_TYPE_UINT32, dicey_type_DICEY_TYPE_UINT64,
+    dicey_type_DICEY_TYPE_UNIT, dicey_type_DICEY_TYPE_UUID, dicey_value, dicey_value_get_array,
+    dicey_value_get_bool, dicey_value_get_byte, dicey_value_get_bytes, dicey_value_get_error,
+    dicey_value_get_float, dicey_value_get_i16, dicey_value_get_i32, dicey_value_get_i64,
+    dicey_value_get_list, dicey_value_get_pair, dicey_value_get_path, dicey_value_get_selector,
+    dicey_value_get_str, dicey_value_get_tuple, dicey_value_get_type, dicey_value_get_u16,
+    dicey_value_get_u32, dicey_value_get_u64, dicey_value_get_uuid,
 };
 
 use uuid::Uuid;
 
-use super::{errors::Error, macros::ccall};
+use super::{MAX_LIST_LEN, MAX_PATH_LEN, errors::Error, macros::ccall, owned::OwnedValue};
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Type {
@@ -63,9 +67,43 @@ pub enum Type {
     Path,
     Selector,
     Error,
+
+    // a type these bindings don't know about, carrying its type byte. Only produced by lenient decoding
+    Unknown(u8),
+
+    // stands for values whose type is only known at runtime, such as views, in place of a kind known in advance. It is
+    // never found on the wire, and libdicey rejects it
+    Any,
 }
 
 impl Type {
+    pub const fn is_container(self) -> bool {
+        matches!(self, Type::Array | Type::Tuple | Type::Pair)
+    }
+
+    // bytes count as unsigned integers
+    pub const fn is_integer(self) -> bool {
+        matches!(
+            self,
+            Type::Byte
+                | Type::Int16
+                | Type::Int32
+                | Type::Int64
+                | Type::UInt16
+                | Type::UInt32
+                | Type::UInt64
+        )
+    }
+
+    pub const fn is_numeric(self) -> bool {
+        self.is_integer() || matches!(self, Type::Float)
+    }
+
+    // true for signed integers and floats
+    pub const fn is_signed(self) -> bool {
+        matches!(self, Type::Float | Type::Int16 | Type::Int32 | Type::Int64)
+    }
+
     pub(crate) const fn to_c(self) -> dicey_type {
         match self {
             Type::Unit => dicey_type_DICEY_TYPE_UNIT,
@@ -87,6 +125,8 @@ impl Type {
             Type::Path => dicey_type_DICEY_TYPE_PATH,
             Type::Selector => dicey_type_DICEY_TYPE_SELECTOR,
             Type::Error => dicey_type_DICEY_TYPE_ERROR,
+            Type::Unknown(b) => b as dicey_type,
+            Type::Any => dicey_type_DICEY_TYPE_INVALID,
         }
     }
 }
@@ -120,7 +160,11 @@ impl TryFrom<dicey_type> for Type {
     }
 }
 
-#[derive(Clone, Debug)]
+/// A value borrowed from a message.
+///
+/// On the wire, multi-byte integers are little-endian and floats are little-endian IEEE 754 doubles, regardless of the
+/// host byte order; UUIDs are the only exception, being stored big-endian as per RFC 4122.
+#[derive(Clone, Debug, PartialEq)]
 pub enum ValueView<'a> {
     Unit,
 
@@ -153,13 +197,207 @@ pub enum ValueView<'a> {
     Selector(Selector<'a>),
 
     Error(ErrorMessage<'a>),
+
+    /// A value of a type these bindings don't know about, only produced by lenient decoding.
+    ///
+    /// libdicey has no way to hand over the encoded contents of a value, so only its type byte is available.
+    Unknown {
+        type_byte: u8,
+    },
+}
+
+// a step into a value, as taken by ValueView::get_path
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Index<'k> {
+    // an item of an array or tuple, or either side of a pair
+    Field(usize),
+
+    // the second item of the first `{s v}` pair with that string as its first, in an array or tuple of pairs
+    Key(&'k str),
+}
+
+impl Index<'_> {
+    const fn field(self) -> Option<usize> {
+        match self {
+            Index::Field(n) => Some(n),
+            Index::Key(_) => None,
+        }
+    }
 }
 
 impl<'a> ValueView<'a> {
+    /// Builds an array, taking its element kind from the items.
+    ///
+    /// All items must have the same kind. An empty array has no items to take the kind from, so it must be written
+    /// as a [`ValueView::Array`] directly.
+    pub fn array(items: Vec<ValueView<'a>>) -> Result<Self, Error> {
+        let element_kind = items.first().ok_or(Error::InvalidData)?.kind();
+
+        if items.iter().any(|item| item.kind() != element_kind) {
+            return Err(Error::ValueTypeMismatch);
+        }
+
+        if items.len() > MAX_LIST_LEN {
+            return Err(Error::ArrayTooLong);
+        }
+
+        Ok(ValueView::Array {
+            element_kind,
+            items,
+        })
+    }
+
+    pub fn pair(first: ValueView<'a>, second: ValueView<'a>) -> Self {
+        ValueView::Pair(Box::new(first), Box::new(second))
+    }
+
+    pub fn tuple(items: Vec<ValueView<'a>>) -> Result<Self, Error> {
+        if items.len() > MAX_LIST_LEN {
+            return Err(Error::ArrayTooLong);
+        }
+
+        Ok(ValueView::Tuple(items))
+    }
+
+    // the declared kind of an array's items, even if it has none
+    pub const fn array_element_kind(&self) -> Option<Type> {
+        match self {
+            ValueView::Array { element_kind, .. } => Some(*element_kind),
+            _ => None,
+        }
+    }
+
+    // renders at most `max_len` items of each bytes, string or list value in the view, for logging large payloads
+    pub fn debug_truncated(&self, max_len: usize) -> impl fmt::Debug {
+        Truncated {
+            value: self,
+            max_len,
+        }
+    }
+
+    /// Splits an error reply into the error and the detail sent along with it, if any.
+    ///
+    /// Besides plain errors, this recognizes the `{e v}` pairs built by [`ErrorMessage::with_detail`]. Any other value
+    /// isn't an error, and gives `None`.
+    pub fn error_with_detail(&self) -> Option<(ErrorMessage<'a>, Option<&ValueView<'a>>)> {
+        match self {
+            ValueView::Error(err) => Some((*err, None)),
+            ValueView::Pair(first, detail) => match **first {
+                ValueView::Error(err) => Some((err, Some(detail))),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     pub fn extract<T: FromDicey<'a>>(&self) -> Result<T, Error> {
         T::from_dicey(self)
     }
 
+    /// Reads a string, or bytes holding one, replacing invalid UTF-8 sequences as [`String::from_utf8_lossy`] does.
+    ///
+    /// This is how to read the strings that lenient decoding couldn't keep as strings, which it hands over as bytes.
+    pub fn str_lossy(&self) -> Option<Cow<'a, str>> {
+        match *self {
+            ValueView::String(s) => Some(Cow::Borrowed(s)),
+            ValueView::Bytes(bytes) => Some(String::from_utf8_lossy(bytes)),
+            _ => None,
+        }
+    }
+
+    /// Extracts an array of fixed-width numbers, failing with [`Error::ValueTypeMismatch`] unless this is an array of
+    /// `T`.
+    ///
+    /// The items have already been decoded by the time a view exists. To skip decoding them at all, read the array
+    /// off the message with [`Message::value_primitive_array`](crate::Message::value_primitive_array) instead.
+    pub fn extract_primitive_array<T: Primitive>(&self) -> Result<Vec<T>, Error> {
+        match self {
+            ValueView::Array {
+                element_kind,
+                items,
+            } if *element_kind == T::KIND => items
+                .iter()
+                .map(|item| T::from_view(item).ok_or(Error::ValueTypeMismatch))
+                .collect(),
+            _ => Err(Error::ValueTypeMismatch),
+        }
+    }
+
+    pub fn to_owned_value(&self) -> OwnedValue {
+        self.into()
+    }
+
+    // tuples are indexed positionally, pairs as if they were tuples of two items
+    pub fn field(&self, index: usize) -> Option<&ValueView<'a>> {
+        match (self, index) {
+            (ValueView::Tuple(items), _) => items.get(index),
+            (ValueView::Pair(first, _), 0) => Some(first),
+            (ValueView::Pair(_, second), 1) => Some(second),
+            _ => None,
+        }
+    }
+
+    pub fn field_as<T: FromDicey<'a>>(&self, index: usize) -> Result<T, Error> {
+        self.field(index)
+            .ok_or(Error::ValueTypeMismatch)
+            .and_then(T::from_dicey)
+    }
+
+    /// Walks nested containers one [`Index`] at a time, returning the value at the end of `path`.
+    ///
+    /// For instance, `[Key("a.Trait"), Key("Op"), Field(1)]` finds the signature of an operation in the value of an
+    /// introspection `Data` property. Gives `None` as soon as a step doesn't lead anywhere.
+    pub fn get_path(&self, path: &[Index<'_>]) -> Option<&ValueView<'a>> {
+        path.iter()
+            .try_fold(self, |value, index| value.child(*index))
+    }
+
+    fn child(&self, index: Index<'_>) -> Option<&ValueView<'a>> {
+        let items = match self {
+            ValueView::Array { items, .. } | ValueView::Tuple(items) => items,
+            _ => return self.field(index.field()?),
+        };
+
+        match index {
+            Index::Field(n) => items.get(n),
+            Index::Key(key) => items.iter().find_map(|item| match item {
+                ValueView::Pair(first, second) if matches!(**first, ValueView::String(s) if s == key) => {
+                    Some(&**second)
+                }
+                _ => None,
+            }),
+        }
+    }
+
+    pub const fn is_container(&self) -> bool {
+        self.kind().is_container()
+    }
+
+    /// Writes the contents of a `Bytes` value to `w` straight from the message, returning how many bytes were written.
+    ///
+    /// Other values fail with an `InvalidData` error wrapping [`Error::ValueTypeMismatch`], and write nothing.
+    pub fn write_bytes_to(&self, mut w: impl io::Write) -> io::Result<usize> {
+        match self {
+            ValueView::Bytes(bytes) => w.write_all(bytes).map(|_| bytes.len()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                Error::ValueTypeMismatch,
+            )),
+        }
+    }
+
+    pub const fn is_integer(&self) -> bool {
+        self.kind().is_integer()
+    }
+
+    pub const fn is_numeric(&self) -> bool {
+        self.kind().is_numeric()
+    }
+
+    pub const fn is_signed(&self) -> bool {
+        self.kind().is_signed()
+    }
+
     pub const fn kind(&self) -> Type {
         match self {
             ValueView::Unit => Type::Unit,
@@ -181,20 +419,147 @@ impl<'a> ValueView<'a> {
             ValueView::Path(_) => Type::Path,
             ValueView::Selector(_) => Type::Selector,
             ValueView::Error(_) => Type::Error,
+            ValueView::Unknown { type_byte } => Type::Unknown(*type_byte),
+        }
+    }
+}
+
+struct Truncated<'v, 'a> {
+    value: &'v ValueView<'a>,
+    max_len: usize,
+}
+
+impl fmt::Debug for Truncated<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let max_len = self.max_len;
+
+        match self.value {
+            ValueView::Array {
+                element_kind,
+                items,
+            } => f
+                .debug_struct("Array")
+                .field("element_kind", element_kind)
+                .field("items", &TruncatedList(items, max_len))
+                .finish(),
+            ValueView::Tuple(items) => f
+                .debug_tuple("Tuple")
+                .field(&TruncatedList(items, max_len))
+                .finish(),
+            ValueView::Pair(first, second) => f
+                .debug_tuple("Pair")
+                .field(&first.debug_truncated(max_len))
+                .field(&second.debug_truncated(max_len))
+                .finish(),
+            ValueView::Bytes(bytes) if bytes.len() > max_len => f
+                .debug_tuple("Bytes")
+                .field(&TruncatedBytes(&bytes[..max_len]))
+                .finish(),
+            ValueView::String(s) => match s.char_indices().nth(max_len) {
+                Some((end, _)) => f
+                    .debug_tuple("String")
+                    .field(&TruncatedStr(&s[..end]))
+                    .finish(),
+                None => self.value.fmt(f),
+            },
+            value => value.fmt(f),
+        }
+    }
+}
+
+struct TruncatedBytes<'v>(&'v [u8]);
+
+impl fmt::Debug for TruncatedBytes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.0).finish_non_exhaustive()
+    }
+}
+
+struct TruncatedList<'v, 'a>(&'v [ValueView<'a>], usize);
+
+impl fmt::Debug for TruncatedList<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let TruncatedList(items, max_len) = *self;
+
+        let mut list = f.debug_list();
+
+        list.entries(
+            items
+                .iter()
+                .take(max_len)
+                .map(|item| item.debug_truncated(max_len)),
+        );
+
+        if items.len() > max_len {
+            list.finish_non_exhaustive()
+        } else {
+            list.finish()
         }
     }
 }
 
+struct TruncatedStr<'v>(&'v str);
+
+impl fmt::Debug for TruncatedStr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}..", self.0)
+    }
+}
+
 impl<'a> FromDicey<'a> for ValueView<'a> {
     fn from_dicey(value: &ValueView<'a>) -> Result<Self, Error> {
         Ok(value.clone())
     }
 }
 
+macro_rules! impl_from_for_view {
+    ($($ty:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl<'a> From<$ty> for ValueView<'a> {
+                fn from(value: $ty) -> Self {
+                    ValueView::$variant(value)
+                }
+            }
+        )*
+    };
+}
+
+// byte slices are left out on purpose, given they may be meant as either bytes or strings
+impl_from_for_view! {
+    bool => Bool,
+    Byte => Byte,
+    f64 => Float,
+    i16 => Int16,
+    i32 => Int32,
+    i64 => Int64,
+    u16 => UInt16,
+    u32 => UInt32,
+    u64 => UInt64,
+    &'a str => String,
+    Uuid => Uuid,
+    &'a Path => Path,
+    Selector<'a> => Selector,
+    ErrorMessage<'a> => Error,
+}
+
+impl From<()> for ValueView<'_> {
+    fn from(_: ()) -> Self {
+        ValueView::Unit
+    }
+}
+
 impl TryFrom<dicey_value> for ValueView<'_> {
     type Error = Error;
 
     fn try_from(value: dicey_value) -> Result<Self, Error> {
+        Self::decode(value, false)
+    }
+}
+
+impl ValueView<'_> {
+    // with `lenient` set, values of unknown types become ValueView::Unknown and strings that aren't UTF-8 are handed
+    // over as ValueView::Bytes, instead of failing the whole decode. So do error messages, which are dropped
+    pub(crate) fn decode(value: dicey_value, lenient: bool) -> Result<Self, Error> {
         let ty = unsafe { dicey_value_get_type(&value) };
 
         unsafe {
@@ -281,9 +646,14 @@ impl TryFrom<dicey_value> for ValueView<'_> {
                         .try_into()
                         .map_err(|_| Error::InvalidData)?;
 
+                    let element_kind = match Type::try_from(ckind) {
+                        Err(_) if lenient => Type::Unknown(ckind as u8),
+                        kind => kind?,
+                    };
+
                     Ok(ValueView::Array {
-                        element_kind: Type::try_from(ckind)?,
-                        items: extract_list(list)?,
+                        element_kind,
+                        items: extract_list(list, lenient)?,
                     })
                 }
 
@@ -292,7 +662,7 @@ impl TryFrom<dicey_value> for ValueView<'_> {
 
                     ccall!(value_get_tuple, &value, &mut ret)?;
 
-                    Ok(ValueView::Tuple(extract_list(ret)?))
+                    Ok(ValueView::Tuple(extract_list(ret, lenient)?))
                 }
 
                 dicey_type_DICEY_TYPE_PAIR => {
@@ -301,8 +671,8 @@ impl TryFrom<dicey_value> for ValueView<'_> {
                     ccall!(value_get_pair, &value, &mut pair)?;
 
                     Ok(ValueView::Pair(
-                        Box::new(ValueView::try_from(pair.first)?),
-                        Box::new(ValueView::try_from(pair.second)?),
+                        Box::new(ValueView::decode(pair.first, lenient)?),
+                        Box::new(ValueView::decode(pair.second, lenient)?),
                     ))
                 }
 
@@ -312,7 +682,7 @@ impl TryFrom<dicey_value> for ValueView<'_> {
 
                     ccall!(value_get_bytes, &value, &mut bytes, &mut nbytes)?;
 
-                    Ok(ValueView::Bytes(slice::from_raw_parts(bytes, nbytes)))
+                    checked_slice(bytes, nbytes).map(ValueView::Bytes)
                 }
 
                 dicey_type_DICEY_TYPE_STR => {
@@ -320,10 +690,13 @@ impl TryFrom<dicey_value> for ValueView<'_> {
 
                     ccall!(value_get_str, &value, &mut bytes)?;
 
-                    CStr::from_ptr(bytes)
-                        .to_str()
-                        .map(ValueView::String)
-                        .map_err(|_| Error::BadMessage)
+                    let s = CStr::from_ptr(bytes);
+
+                    match s.to_str() {
+                        Ok(s) => Ok(ValueView::String(s)),
+                        Err(_) if lenient => Ok(ValueView::Bytes(s.to_bytes())),
+                        Err(_) => Err(Error::BadMessage),
+                    }
                 }
 
                 dicey_type_DICEY_TYPE_UUID => {
@@ -358,15 +731,189 @@ impl TryFrom<dicey_value> for ValueView<'_> {
 
                     ccall!(value_get_error, &value, &mut error)?;
 
-                    Ok(ValueView::Error(ErrorMessage::from(error)))
+                    let err = ErrorMessage::from(error);
+
+                    if !lenient && err.message.is_none() && !error.message.is_null() {
+                        return Err(Error::BadMessage);
+                    }
+
+                    Ok(ValueView::Error(err))
                 }
 
+                _ if lenient => Ok(ValueView::Unknown {
+                    type_byte: ty as u8,
+                }),
+
                 _ => Err(Error::BadMessage),
             }
         }
     }
 }
 
+/// A lazy iterator over the items of a received array or tuple.
+///
+/// Items are decoded on demand, without collecting them into a `Vec` first. The iterator borrows from the message
+/// that contains the list, and cannot outlive it. After an item fails to decode, the iterator ends.
+#[derive(Clone)]
+pub struct ListIter<'a> {
+    citer: dicey_iterator,
+    element_kind: Option<Type>,
+
+    failed: bool,
+
+    _marker: PhantomData<&'a ()>,
+}
+
+impl<'a> ListIter<'a> {
+    pub const fn element_kind(&self) -> Option<Type> {
+        self.element_kind
+    }
+
+    // reads the items straight into a Vec, without decoding each of them into a ValueView first
+    pub(crate) fn read_primitive_array<T: Primitive>(self) -> Result<Vec<T>, Error> {
+        if self.element_kind != Some(T::KIND) {
+            return Err(Error::ValueTypeMismatch);
+        }
+
+        let mut citer = self.citer;
+        let mut items = Vec::new();
+
+        unsafe {
+            while dicey_iterator_has_next(citer) {
+                let mut value = mem::zeroed();
+
+                ccall!(iterator_next, &mut citer, &mut value)?;
+
+                items.push(T::read(&value)?);
+            }
+        }
+
+        Ok(items)
+    }
+
+    // decodes only the first half of the next item, which must be a pair, leaving the second one untouched
+    pub(crate) fn next_pair_first(&mut self) -> Option<Result<ValueView<'a>, Error>> {
+        self.next_with(|value| unsafe {
+            let mut pair: dicey_pair = mem::zeroed();
+
+            ccall!(value_get_pair, &value, &mut pair)?;
+
+            ValueView::try_from(pair.first)
+        })
+    }
+
+    fn next_with<F>(&mut self, decode: F) -> Option<Result<ValueView<'a>, Error>>
+    where
+        F: FnOnce(dicey_value) -> Result<ValueView<'a>, Error>,
+    {
+        if self.failed || !unsafe { dicey_iterator_has_next(self.citer) } {
+            return None;
+        }
+
+        let mut value = unsafe { mem::zeroed() };
+
+        let res = unsafe { ccall!(iterator_next, &mut self.citer, &mut value) }
+            .and_then(|_| decode(value));
+
+        self.failed = res.is_err();
+
+        Some(res)
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+// the fixed-width numeric types, which can be read out of a dicey value directly
+pub trait Primitive: sealed::Sealed + Sized {
+    const KIND: Type;
+
+    #[doc(hidden)]
+    unsafe fn read(value: &dicey_value) -> Result<Self, Error>;
+
+    #[doc(hidden)]
+    fn from_view(view: &ValueView<'_>) -> Option<Self>;
+}
+
+macro_rules! impl_primitive {
+    ($($ty:ty => $kind:ident, $getter:ident);+ $(;)?) => {
+        $(
+            impl sealed::Sealed for $ty {}
+
+            impl Primitive for $ty {
+                const KIND: Type = Type::$kind;
+
+                unsafe fn read(value: &dicey_value) -> Result<Self, Error> {
+                    let mut ret = Default::default();
+
+                    unsafe { ccall!($getter, value, &mut ret) }?;
+
+                    Ok(ret)
+                }
+
+                fn from_view(view: &ValueView<'_>) -> Option<Self> {
+                    match view {
+                        ValueView::$kind(value) => Some((*value).into()),
+                        _ => None,
+                    }
+                }
+            }
+        )+
+    };
+}
+
+impl_primitive! {
+    u8 => Byte, value_get_byte;
+    f64 => Float, value_get_float;
+    i16 => Int16, value_get_i16;
+    i32 => Int32, value_get_i32;
+    i64 => Int64, value_get_i64;
+    u16 => UInt16, value_get_u16;
+    u32 => UInt32, value_get_u32;
+    u64 => UInt64, value_get_u64;
+}
+
+impl<'a> Iterator for ListIter<'a> {
+    type Item = Result<ValueView<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with(ValueView::try_from)
+    }
+}
+
+impl FusedIterator for ListIter<'_> {}
+
+impl TryFrom<dicey_value> for ListIter<'_> {
+    type Error = Error;
+
+    fn try_from(value: dicey_value) -> Result<Self, Error> {
+        unsafe {
+            let mut list: dicey_list = mem::zeroed();
+
+            ccall!(value_get_list, &value, &mut list)?;
+
+            let element_kind = if dicey_value_get_type(&value) == dicey_type_DICEY_TYPE_ARRAY {
+                let ckind: dicey_type = dicey_list_type(&list)
+                    .try_into()
+                    .map_err(|_| Error::InvalidData)?;
+
+                Some(Type::try_from(ckind)?)
+            } else {
+                None
+            };
+
+            Ok(ListIter {
+                citer: dicey_list_iter(&list),
+                element_kind,
+                failed: false,
+
+                _marker: PhantomData,
+            })
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct Byte(pub u8);
 
@@ -382,22 +929,103 @@ impl From<Byte> for u8 {
     }
 }
 
+macro_rules! impl_from_byte {
+    ($($ty:ty),+) => {
+        $(
+            impl From<Byte> for $ty {
+                fn from(byte: Byte) -> Self {
+                    byte.0.into()
+                }
+            }
+        )+
+    };
+}
+
+impl_from_byte!(i16, i32, i64, u16, u32, u64, usize);
+
+impl AsRef<u8> for Byte {
+    fn as_ref(&self) -> &u8 {
+        &self.0
+    }
+}
+
+impl Deref for Byte {
+    type Target = u8;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl PartialEq<u8> for Byte {
+    fn eq(&self, other: &u8) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<Byte> for u8 {
+    fn eq(&self, other: &Byte) -> bool {
+        *self == other.0
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct ErrorMessage<'a> {
     pub code: i16,
     pub message: Option<&'a str>,
 }
 
+impl<'a> ErrorMessage<'a> {
+    pub fn new(code: i16, message: impl Into<Option<&'a str>>) -> Self {
+        ErrorMessage {
+            code,
+            message: message.into(),
+        }
+    }
+
+    // returns the library error this message carries, if any; servers are free to use codes of their own
+    pub fn as_error(&self) -> Option<Error> {
+        Error::ALL
+            .iter()
+            .copied()
+            .find(|err| err.code() == i32::from(self.code))
+    }
+
+    // the error to fail a request with. Codes of the server's own become InvalidData, as libdicey does for error replies
+    // it can't make sense of
+    pub(crate) fn into_error(self) -> Error {
+        match self.as_error() {
+            Some(err) if err != Error::OK => err,
+            _ => Error::InvalidData,
+        }
+    }
+
+    /// Pairs this error with a structured detail, to be sent as a `{e v}` pair.
+    ///
+    /// Error values on the wire only carry a code and an optional string, with no room for anything else. Services that
+    /// need richer errors send the detail next to the error instead; [`ValueView::error_with_detail`] takes such a pair
+    /// apart again, and `(ErrorMessage, T)` can be extracted directly when the detail's type is known.
+    pub const fn with_detail<T>(self, detail: T) -> (Self, T) {
+        (self, detail)
+    }
+}
+
+// all library error codes fit in 16 bits
+impl From<Error> for ErrorMessage<'static> {
+    fn from(err: Error) -> Self {
+        ErrorMessage::new(err.code() as i16, err.message())
+    }
+}
+
+// messages that aren't UTF-8 are dropped, leaving only the code
 impl From<dicey_errmsg> for ErrorMessage<'_> {
     fn from(c_error: dicey_errmsg) -> Self {
         let message = if c_error.message.is_null() {
             None
         } else {
-            Some(unsafe {
-                std::ffi::CStr::from_ptr(c_error.message)
-                    .to_str()
-                    .expect("error messages must be ASCII")
-            })
+            unsafe { std::ffi::CStr::from_ptr(c_error.message) }
+                .to_str()
+                .ok()
         };
 
         ErrorMessage {
@@ -412,9 +1040,59 @@ pub struct Path(str);
 
 impl Path {
     pub fn new<S: AsRef<str> + ?Sized>(s: &S) -> &Path {
-        // magic: we take the str and we magically mutate it into a Dicey Path (no verification for now, we really can't)
+        // magic: we take the str and we magically mutate it into a Dicey Path (no verification, see try_new)
         unsafe { &*(s.as_ref() as *const str as *const Path) }
     }
+
+    /// Wraps `s` after checking it against the rules servers apply to object paths: it must start with a `/`, must not
+    /// end with one, must not contain NULs and must fit in [`MAX_PATH_LEN`](crate::MAX_PATH_LEN) bytes.
+    ///
+    /// Use the [`path!`](crate::path) macro to check literals at compile time instead.
+    pub const fn try_new(s: &str) -> Result<&Path, Error> {
+        let bytes = s.as_bytes();
+
+        if bytes.len() > MAX_PATH_LEN {
+            return Err(Error::PathTooLong);
+        }
+
+        match bytes {
+            [b'/', .., last] if *last != b'/' => {}
+            _ => return Err(Error::MalformedPath),
+        }
+
+        let mut i = 0;
+
+        while i < bytes.len() {
+            if bytes[i] == 0 {
+                return Err(Error::MalformedPath);
+            }
+
+            i += 1;
+        }
+
+        Ok(unsafe { &*(s as *const str as *const Path) })
+    }
+}
+
+/// Checks a path literal at compile time, yielding a `&'static Path`.
+///
+/// ```
+/// const ECHO_PATH: &dicey::Path = dicey::path!("/dicey/test/echo");
+/// ```
+///
+/// ```compile_fail
+/// const BAD_PATH: &dicey::Path = dicey::path!("dicey/test/echo/");
+/// ```
+#[macro_export]
+macro_rules! path {
+    ($path:literal) => {{
+        const PATH: &'static $crate::Path = match $crate::Path::try_new($path) {
+            Ok(path) => path,
+            Err(_) => panic!(concat!("malformed dicey path: ", $path)),
+        };
+
+        PATH
+    }};
 }
 
 impl AsRef<str> for Path {
@@ -423,6 +1101,18 @@ impl AsRef<str> for Path {
     }
 }
 
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&Path> for Vec<u8> {
+    fn from(path: &Path) -> Self {
+        path.0.as_bytes().to_vec()
+    }
+}
+
 impl Deref for Path {
     type Target = str;
 
@@ -440,7 +1130,43 @@ impl ToOwned for Path {
 }
 
 pub(crate) fn bytes_to_cpath(path: impl Into<Vec<u8>>) -> Result<CString, Error> {
-    CString::new(path.into()).map_err(|_| Error::MalformedPath)
+    let mut path = path.into();
+
+    let len = normalize_cstr_bytes(&path)
+        .map_err(|_| Error::MalformedPath)?
+        .len();
+
+    if len > MAX_PATH_LEN {
+        return Err(Error::PathTooLong);
+    }
+
+    path.truncate(len);
+
+    CString::new(path).map_err(|_| Error::MalformedPath)
+}
+
+pub(crate) fn bytes_to_cstring(bytes: &[u8]) -> Result<CString, Error> {
+    CString::new(normalize_cstr_bytes(bytes)?).map_err(|_| Error::InvalidData)
+}
+
+// the rules for any bytes that end up in a C string: a single trailing NUL is dropped, given that the constants coming
+// from libdicey are null-terminated, and anything else containing NULs is rejected
+pub(crate) fn normalize_cstr_bytes(bytes: &[u8]) -> Result<&[u8], Error> {
+    let bytes = strip_null(bytes);
+
+    if bytes.contains(&0) {
+        Err(Error::InvalidData)
+    } else {
+        Ok(bytes)
+    }
+}
+
+fn strip_null(bytes: &[u8]) -> &[u8] {
+    if bytes.ends_with(&[0]) {
+        &bytes[..bytes.len() - 1]
+    } else {
+        bytes
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -496,6 +1222,20 @@ impl<'a> From<(&'a [u8], &'a [u8])> for Selector<'a> {
     }
 }
 
+// parses a "trait:elem" selector. Trait names contain dots but not colons, so the string is split on its last colon
+impl<'a> TryFrom<&'a str> for Selector<'a> {
+    type Error = Error;
+
+    fn try_from(sel: &'a str) -> Result<Self, Error> {
+        match sel.rsplit_once(':') {
+            Some((trait_name, elem)) if !trait_name.is_empty() && !elem.is_empty() => {
+                Ok((trait_name, elem).into())
+            }
+            _ => Err(Error::InvalidData),
+        }
+    }
+}
+
 impl From<dicey_selector> for Selector<'_> {
     fn from(c_selector: dicey_selector) -> Self {
         let (trait_name, elem) = unsafe {
@@ -509,6 +1249,101 @@ impl From<dicey_selector> for Selector<'_> {
     }
 }
 
+impl<'a> Selector<'a> {
+    /// Borrows a selector from buffers owned by the caller, such as a `Vec<u8>` or a null-terminated constant.
+    ///
+    /// A single trailing NUL is stripped from both parts, so the selector is the same whether the buffers are
+    /// null-terminated or not.
+    pub fn from_owned(trait_name: &'a [u8], elem: &'a [u8]) -> Self {
+        Selector {
+            trait_name: strip_null(trait_name),
+            elem: strip_null(elem),
+        }
+    }
+
+    pub fn to_owned(&self) -> SelectorBuf {
+        SelectorBuf {
+            trait_name: self.trait_name.to_vec(),
+            elem: self.elem.to_vec(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct SelectorBuf {
+    pub trait_name: Vec<u8>,
+    pub elem: Vec<u8>,
+}
+
+impl SelectorBuf {
+    pub fn as_selector(&self) -> Selector<'_> {
+        Selector {
+            trait_name: &self.trait_name,
+            elem: &self.elem,
+        }
+    }
+
+    pub fn selector(&self) -> Selector<'_> {
+        self.as_selector()
+    }
+}
+
+impl From<Selector<'_>> for SelectorBuf {
+    fn from(sel: Selector<'_>) -> Self {
+        sel.to_owned()
+    }
+}
+
+impl<'a> From<&'a SelectorBuf> for Selector<'a> {
+    fn from(sel: &'a SelectorBuf) -> Self {
+        sel.as_selector()
+    }
+}
+
+/// A two-element tuple value.
+///
+/// Rust pairs `(A, B)` map to dicey pairs (`{..}`), while tuples of one or three and more elements map to dicey tuples
+/// (`(..)`). Use this to send or receive a tuple with exactly two elements.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Tuple2<A, B>(pub A, pub B);
+
+impl<A, B> From<(A, B)> for Tuple2<A, B> {
+    fn from((a, b): (A, B)) -> Self {
+        Tuple2(a, b)
+    }
+}
+
+impl<A, B> From<Tuple2<A, B>> for (A, B) {
+    fn from(Tuple2(a, b): Tuple2<A, B>) -> Self {
+        (a, b)
+    }
+}
+
+/// Packs operation arguments into the tuple dicey expects.
+///
+/// No arguments become unit, and two arguments become a [`Tuple2`] rather than a pair; any other count becomes a tuple
+/// of that size. A single value that isn't wrapped in a tuple can be passed to `exec` or `call` as-is.
+///
+/// ```
+/// assert_eq!(dicey::args!(1i32, "two"), dicey::Tuple2(1i32, "two"));
+/// assert_eq!(dicey::args!(1i32, "two", 3.0), (1i32, "two", 3.0));
+/// ```
+#[macro_export]
+macro_rules! args {
+    () => {
+        ()
+    };
+    ($a:expr $(,)?) => {
+        ($a,)
+    };
+    ($a:expr, $b:expr $(,)?) => {
+        $crate::Tuple2($a, $b)
+    };
+    ($($arg:expr),+ $(,)?) => {
+        ($($arg,)+)
+    };
+}
+
 pub trait FromDicey<'a>: Sized {
     fn from_dicey(value: &ValueView<'a>) -> Result<Self, Error>;
 }
@@ -549,6 +1384,21 @@ impl FromDicey<'_> for f64 {
     }
 }
 
+// the wire only carries doubles, so narrowing is lossy by nature; only refuse values that would silently become
+// infinite
+impl FromDicey<'_> for f32 {
+    fn from_dicey(value: &ValueView<'_>) -> Result<Self, Error> {
+        let f = f64::from_dicey(value)?;
+        let narrow = f as f32;
+
+        if f.is_finite() && narrow.is_infinite() {
+            return Err(Error::Overflow);
+        }
+
+        Ok(narrow)
+    }
+}
+
 impl FromDicey<'_> for i16 {
     fn from_dicey(value: &ValueView<'_>) -> Result<Self, Error> {
         match value {
@@ -612,6 +1462,30 @@ impl FromDicey<'_> for Uuid {
     }
 }
 
+impl<'a> FromDicey<'a> for ErrorMessage<'a> {
+    fn from_dicey(value: &ValueView<'a>) -> Result<Self, Error> {
+        match value {
+            ValueView::Error(err) => Ok(*err),
+            _ => Err(Error::ValueTypeMismatch),
+        }
+    }
+}
+
+impl<'a> FromDicey<'a> for Selector<'a> {
+    fn from_dicey(value: &ValueView<'a>) -> Result<Self, Error> {
+        match value {
+            ValueView::Selector(sel) => Ok(*sel),
+            _ => Err(Error::ValueTypeMismatch),
+        }
+    }
+}
+
+impl FromDicey<'_> for SelectorBuf {
+    fn from_dicey(value: &ValueView<'_>) -> Result<Self, Error> {
+        Selector::from_dicey(value).map(SelectorBuf::from)
+    }
+}
+
 impl<'a, T: FromDicey<'a>> FromDicey<'a> for Vec<T> {
     fn from_dicey(value: &ValueView<'a>) -> Result<Self, Error> {
         match value {
@@ -642,7 +1516,8 @@ macro_rules! impl_from_dicey_tuple {
     };
 }
 
-// implement FromDicey for tuples of size 1 to 32
+// implement FromDicey for tuples of size 1 to 32. Two-element Rust tuples are pairs, see Tuple2
+impl_from_dicey_tuple!(A);
 impl_from_dicey_tuple!(A B C);
 impl_from_dicey_tuple!(A B C D);
 impl_from_dicey_tuple!(A B C D E);
@@ -687,6 +1562,21 @@ where
     }
 }
 
+impl<'a, A, B> FromDicey<'a> for Tuple2<A, B>
+where
+    A: FromDicey<'a>,
+    B: FromDicey<'a>,
+{
+    fn from_dicey(value: &ValueView<'a>) -> Result<Self, Error> {
+        match value {
+            ValueView::Tuple(items) if items.len() == 2 => {
+                Ok(Tuple2(A::from_dicey(&items[0])?, B::from_dicey(&items[1])?))
+            }
+            _ => Err(Error::ValueTypeMismatch),
+        }
+    }
+}
+
 impl<'a> FromDicey<'a> for &'a [u8] {
     fn from_dicey(value: &ValueView<'a>) -> Result<Self, Error> {
         match value {
@@ -717,7 +1607,37 @@ impl<'a> FromDicey<'a> for String {
     }
 }
 
-unsafe fn extract_list<'a>(list: dicey_list) -> Result<Vec<ValueView<'a>>, Error> {
+impl<'a> FromDicey<'a> for Cow<'a, [u8]> {
+    fn from_dicey(value: &ValueView<'a>) -> Result<Self, Error> {
+        value.extract::<&[u8]>().map(Cow::Borrowed)
+    }
+}
+
+impl<'a> FromDicey<'a> for Cow<'a, str> {
+    fn from_dicey(value: &ValueView<'a>) -> Result<Self, Error> {
+        value.extract::<&str>().map(Cow::Borrowed)
+    }
+}
+
+// the length comes from the wire, so never trust it blindly: slices longer than isize::MAX are UB, and that limit is
+// very much reachable on 32-bit targets
+unsafe fn checked_slice<'a>(data: *const u8, len: usize) -> Result<&'a [u8], Error> {
+    if len == 0 {
+        return Ok(&[]);
+    }
+
+    if data.is_null() {
+        return Err(Error::InvalidData);
+    }
+
+    if isize::try_from(len).is_err() {
+        return Err(Error::Overflow);
+    }
+
+    Ok(unsafe { slice::from_raw_parts(data, len) })
+}
+
+unsafe fn extract_list<'a>(list: dicey_list, lenient: bool) -> Result<Vec<ValueView<'a>>, Error> {
     let mut ret = Vec::new();
 
     unsafe {
@@ -728,9 +1648,494 @@ unsafe fn extract_list<'a>(list: dicey_list) -> Result<Vec<ValueView<'a>>, Error
 
             ccall!(iterator_next, &mut iter, &mut value)?;
 
-            ret.push(ValueView::try_from(value)?);
+            ret.push(ValueView::decode(value, lenient)?);
         }
     }
 
     Ok(ret)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{Message, MessageBuilder, core::tests::set_value};
+
+    use super::*;
+
+    // encodes the value, reloads the message from its raw bytes, and checks the encoding is the expected one
+    fn reload_numeric<T>(value: T, wire: &[u8]) -> T
+    where
+        T: crate::ToDicey + for<'a> FromDicey<'a>,
+    {
+        let msg = set_value(value);
+        let raw = msg.raw_bytes();
+
+        // the value comes last in the packet, after its type byte
+        assert_eq!(&raw[raw.len() - wire.len()..], wire);
+        assert_eq!(raw[raw.len() - wire.len() - 1], T::TYPE_KIND.to_c() as u8);
+
+        let reloaded = Message::try_from(crate::Packet::load(raw).unwrap()).unwrap();
+
+        T::from_dicey(&reloaded.value().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_byte_order() {
+        macro_rules! check_ints {
+            ($($value:expr),* $(,)?) => {
+                $(assert_eq!(reload_numeric($value, &$value.to_le_bytes()), $value);)*
+            };
+        }
+
+        check_ints!(
+            0x0102i16,
+            i16::MIN,
+            0x0102_0304i32,
+            i32::MIN,
+            0x0102_0304_0506_0708i64,
+            i64::MIN,
+            i64::MAX,
+            0x0102u16,
+            u16::MAX,
+            0x0102_0304u32,
+            0x0102_0304_0506_0708u64,
+            u64::MAX,
+        );
+
+        let floats = [
+            0.0,
+            -0.0,
+            1.5,
+            f64::MIN_POSITIVE,
+            f64::MIN_POSITIVE / 2.0, // subnormal
+            f64::from_bits(1),       // smallest subnormal
+            f64::MAX,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NAN,
+            f64::from_bits(0x7ff8_0000_dead_beef), // NaN with a payload
+        ];
+
+        for f in floats {
+            let reloaded = reload_numeric(f, &f.to_le_bytes());
+            assert_eq!(reloaded.to_bits(), f.to_bits());
+        }
+    }
+
+    #[test]
+    fn test_primitive_array() {
+        let floats: Vec<f64> = (0..1000).map(|i| i as f64 / 3.0).collect();
+        let msg = set_value(floats.clone());
+
+        assert_eq!(msg.value_primitive_array::<f64>().unwrap(), floats);
+
+        // same as the view and the generic path
+        let value = msg.value().unwrap();
+        assert_eq!(value.extract_primitive_array::<f64>().unwrap(), floats);
+        assert_eq!(value.extract::<Vec<f64>>().unwrap(), floats);
+
+        assert_eq!(
+            msg.value_primitive_array::<u64>(),
+            Err(Error::ValueTypeMismatch)
+        );
+        assert_eq!(
+            value.extract_primitive_array::<u64>(),
+            Err(Error::ValueTypeMismatch)
+        );
+
+        let msg = set_value(vec![Byte(1), Byte(2)]);
+        assert_eq!(msg.value_primitive_array::<u8>().unwrap(), [1, 2]);
+        assert_eq!(
+            msg.value()
+                .unwrap()
+                .extract_primitive_array::<u8>()
+                .unwrap(),
+            [1, 2]
+        );
+
+        let msg = set_value(vec![Tuple2(1i32, "a")]);
+        assert_eq!(
+            msg.value_primitive_array::<i32>(),
+            Err(Error::ValueTypeMismatch)
+        );
+        assert_eq!(
+            msg.value().unwrap().extract_primitive_array::<i32>(),
+            Err(Error::ValueTypeMismatch)
+        );
+    }
+
+    #[test]
+    fn test_raw_bytes_reload() {
+        let msg = set_value(Selector::from(("b.Other", "Elem")));
+
+        let reloaded = crate::Packet::load(msg.raw_bytes()).unwrap();
+
+        assert_eq!(reloaded.raw_bytes(), msg.raw_bytes());
+        assert_eq!(
+            crate::Packet::classify(msg.raw_bytes()),
+            Ok(crate::PacketKind::Message)
+        );
+        assert_eq!(reloaded.kind(), crate::PacketKind::Message);
+    }
+
+    #[test]
+    fn test_tuple_shapes() {
+        let msg = set_value((1i32, "a"));
+        assert!(matches!(msg.value(), Some(ValueView::Pair(_, _))));
+        assert_eq!(
+            <(i32, &str)>::from_dicey(&msg.value().unwrap()),
+            Ok((1, "a"))
+        );
+        assert!(Tuple2::<i32, &str>::from_dicey(&msg.value().unwrap()).is_err());
+
+        let msg = set_value(Tuple2(1i32, "a"));
+        assert!(matches!(msg.value(), Some(ValueView::Tuple(ref items)) if items.len() == 2));
+        assert_eq!(
+            Tuple2::<i32, &str>::from_dicey(&msg.value().unwrap()),
+            Ok(Tuple2(1, "a"))
+        );
+
+        let msg = set_value((true,));
+        assert_eq!(<(bool,)>::from_dicey(&msg.value().unwrap()), Ok((true,)));
+    }
+
+    #[test]
+    fn test_from_primitives() {
+        let msg = set_value(5u32);
+        assert_eq!(msg.value(), Some(ValueView::from(5u32)));
+
+        let msg = set_value("hello");
+        assert_eq!(msg.value(), Some("hello".into()));
+
+        assert_eq!(ValueView::from(()), ValueView::Unit);
+        assert_eq!(ValueView::from(true), ValueView::Bool(true));
+        assert_eq!(ValueView::from(-1i16), ValueView::Int16(-1));
+    }
+
+    #[test]
+    fn test_kind_predicates() {
+        assert!(ValueView::from(5u32).is_integer());
+        assert!(!ValueView::from(5u32).is_signed());
+        assert!(ValueView::from(-1i64).is_signed());
+        assert!(ValueView::from(1.5).is_numeric());
+        assert!(!ValueView::from(1.5).is_integer());
+        assert!(!ValueView::from("a").is_numeric());
+        assert!(ValueView::Tuple(vec![]).is_container());
+        assert!(Type::Byte.is_integer() && !Type::Byte.is_signed());
+
+        let empty = ValueView::Array {
+            element_kind: Type::Path,
+            items: vec![],
+        };
+        assert_eq!(empty.array_element_kind(), Some(Type::Path));
+        assert_eq!(ValueView::Tuple(vec![]).array_element_kind(), None);
+    }
+
+    #[test]
+    fn test_debug_truncated() {
+        let value = ValueView::Tuple(vec![
+            ValueView::Bytes(&[1, 2, 3, 4]),
+            ValueView::String("àèìòù"),
+            ValueView::Array {
+                element_kind: Type::UInt16,
+                items: vec![ValueView::UInt16(1), ValueView::UInt16(2)],
+            },
+        ]);
+
+        assert_eq!(
+            format!("{:?}", value.debug_truncated(2)),
+            r#"Tuple([Bytes([1, 2, ..]), String("àè"..), ..])"#
+        );
+        assert_eq!(
+            format!("{:?}", value.debug_truncated(8)),
+            format!("{value:?}")
+        );
+    }
+
+    #[test]
+    fn test_write_bytes_to() {
+        let mut out = Vec::new();
+
+        assert_eq!(
+            ValueView::Bytes(b"\x00\x01\x02")
+                .write_bytes_to(&mut out)
+                .unwrap(),
+            3
+        );
+        assert_eq!(out, [0, 1, 2]);
+
+        let err = ValueView::from("abc").write_bytes_to(&mut out).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(out.len(), 3);
+    }
+
+    #[test]
+    fn test_checked_paths() {
+        assert_eq!(Path::try_new("/a/b").map(|p| &**p), Ok("/a/b"));
+        assert_eq!(&**path!("/dicey/server"), "/dicey/server");
+
+        for path in ["", "/", "a/b", "/a/", "/a\0b"] {
+            assert_eq!(Path::try_new(path), Err(Error::MalformedPath), "{path:?}");
+        }
+    }
+
+    #[test]
+    fn test_selector_roundtrip() {
+        let owned = {
+            let msg = set_value(Selector::from(("b.Other", "Elem")));
+
+            SelectorBuf::from_dicey(&msg.value().unwrap()).unwrap()
+        };
+
+        assert_eq!(owned.as_selector(), Selector::from(("b.Other", "Elem")));
+
+        let msg = set_value(owned.clone());
+
+        assert_eq!(
+            Selector::from_dicey(&msg.value().unwrap()).unwrap(),
+            owned.as_selector()
+        );
+
+        let (trait_name, elem) = (b"b.Other\0".to_vec(), b"Elem".to_vec());
+
+        assert_eq!(Selector::from_owned(&trait_name, &elem), owned.selector());
+    }
+
+    #[test]
+    fn test_selector_from_str() {
+        assert_eq!(
+            Selector::try_from("dicey.test.Echo:Echo"),
+            Ok(Selector::from(("dicey.test.Echo", "Echo")))
+        );
+        assert_eq!(
+            Selector::try_from("a:b:c"),
+            Ok(Selector::from(("a:b", "c")))
+        );
+
+        for sel in ["a.Trait", ":Elem", "a.Trait:", ":"] {
+            assert_eq!(Selector::try_from(sel), Err(Error::InvalidData), "{sel:?}");
+        }
+    }
+
+    #[test]
+    fn test_null_terminators() {
+        assert_eq!(normalize_cstr_bytes(b"a.Trait\0"), Ok(&b"a.Trait"[..]));
+        assert_eq!(normalize_cstr_bytes(b"a.Trait"), Ok(&b"a.Trait"[..]));
+        assert_eq!(normalize_cstr_bytes(b"a\0Trait"), Err(Error::InvalidData));
+        assert_eq!(
+            normalize_cstr_bytes(b"a.Trait\0\0"),
+            Err(Error::InvalidData)
+        );
+
+        assert_eq!(bytes_to_cpath("/foo\0").unwrap().as_bytes(), b"/foo");
+        assert_eq!(bytes_to_cpath("/f\0oo"), Err(Error::MalformedPath));
+
+        let build = |path: &[u8], sel: Selector| {
+            MessageBuilder::get()
+                .unwrap()
+                .path(path)
+                .unwrap()
+                .selector(sel)
+                .unwrap()
+                .build()
+                .unwrap()
+        };
+
+        let plain = build(b"/foo", Selector::from(("a.Trait", "Prop")));
+        let terminated = build(
+            b"/foo\0",
+            Selector::from((&b"a.Trait\0"[..], &b"Prop\0"[..])),
+        );
+
+        assert_eq!(plain.raw_bytes(), terminated.raw_bytes());
+
+        let msg = set_value(Selector::from((&b"b.Other\0"[..], &b"Elem"[..])));
+
+        assert_eq!(
+            Selector::from_dicey(&msg.value().unwrap()),
+            Ok(Selector::from(("b.Other", "Elem")))
+        );
+    }
+
+    #[test]
+    fn test_error_message_roundtrip() {
+        let msg = set_value(ErrorMessage::from(Error::PathNotFound));
+
+        match msg.value() {
+            Some(ValueView::Error(e)) => {
+                assert_eq!(e.message, Some(Error::PathNotFound.message()));
+                assert_eq!(e.as_error(), Some(Error::PathNotFound));
+            }
+            _ => panic!("expected an error value"),
+        }
+
+        assert_eq!(ErrorMessage::new(42, None).as_error(), None);
+    }
+
+    #[test]
+    fn test_error_with_detail() {
+        let msg = set_value(ErrorMessage::new(-7, "bad input").with_detail(("field", 3u32)));
+        let value = msg.value().unwrap();
+
+        let (err, detail) = value.error_with_detail().unwrap();
+        assert_eq!(err, ErrorMessage::new(-7, "bad input"));
+        assert_eq!(detail.unwrap().extract::<(&str, u32)>(), Ok(("field", 3)));
+
+        assert_eq!(
+            value.extract::<(ErrorMessage, (&str, u32))>(),
+            Ok((ErrorMessage::new(-7, "bad input"), ("field", 3)))
+        );
+
+        let msg = set_value(ErrorMessage::new(-7, None));
+        assert_eq!(
+            msg.value().unwrap().error_with_detail(),
+            Some((ErrorMessage::new(-7, None), None))
+        );
+
+        let msg = set_value((1u32, 2u32));
+        assert_eq!(msg.value().unwrap().error_with_detail(), None);
+    }
+
+    #[test]
+    fn test_non_utf8() {
+        let latin1 = CString::new(&b"caf\xe9"[..]).unwrap();
+
+        let msg = set_value(vec![latin1.clone(), c"ok".to_owned()]);
+        let value = msg.value_lenient().unwrap();
+
+        assert_eq!(
+            value.get_path(&[Index::Field(1)]),
+            Some(&ValueView::String("ok"))
+        );
+
+        // the string is handed over as its raw bytes, which can still be read as a string
+        let text = value.get_path(&[Index::Field(0)]).unwrap();
+        assert_eq!(text, &ValueView::Bytes(b"caf\xe9"));
+        assert_eq!(text.extract::<&str>(), Err(Error::ValueTypeMismatch));
+        assert_eq!(text.str_lossy(), Some(Cow::Borrowed("caf\u{fffd}")));
+        assert_eq!(
+            value.get_path(&[Index::Field(1)]).unwrap().str_lossy(),
+            Some(Cow::Borrowed("ok"))
+        );
+
+        // paths are kept as they were sent, and only replaced when read as a string
+        let msg = MessageBuilder::set()
+            .unwrap()
+            .path(&b"/caf\xe9"[..])
+            .unwrap()
+            .selector(("a.Trait", "Prop"))
+            .unwrap()
+            .value(())
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(msg.path(), "/caf\u{fffd}");
+        assert_eq!(msg.path_bytes(), b"/caf\xe9");
+
+        let reply = crate::Packet::load(msg.raw_bytes())
+            .unwrap()
+            .as_message()
+            .unwrap()
+            .respond(())
+            .unwrap();
+        assert_eq!(reply.path_bytes(), b"/caf\xe9");
+    }
+
+    #[test]
+    fn test_list_iter_fused() {
+        let latin1 = CString::new(&b"caf\xe9"[..]).unwrap();
+
+        let msg = set_value(vec![latin1, c"ok".to_owned()]);
+        let mut iter = msg.value_array_iter().unwrap();
+
+        assert_eq!(iter.next(), Some(Err(Error::BadMessage)));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn test_view_constructors() {
+        let array = ValueView::array(vec![ValueView::Int32(1), ValueView::Int32(2)]).unwrap();
+        assert_eq!(array.array_element_kind(), Some(Type::Int32));
+
+        assert_eq!(
+            ValueView::array(vec![ValueView::Int32(1), ValueView::String("a")]),
+            Err(Error::ValueTypeMismatch)
+        );
+        assert_eq!(ValueView::array(vec![]), Err(Error::InvalidData));
+        assert_eq!(
+            ValueView::tuple(vec![ValueView::Unit; MAX_LIST_LEN + 1]),
+            Err(Error::ArrayTooLong)
+        );
+
+        let view = ValueView::tuple(vec![
+            array,
+            ValueView::pair(ValueView::String("a"), ValueView::Bool(true)),
+        ])
+        .unwrap();
+
+        let msg = set_value(view.clone());
+        assert_eq!(msg.value().unwrap(), view);
+    }
+
+    #[test]
+    fn test_get_path() {
+        let msg = set_value(vec![
+            ("a.A", vec![("Op", Tuple2(Byte(b'>'), "s -> s"))]),
+            ("b.B", vec![("Prop", Tuple2(Byte(b'P'), "u"))]),
+        ]);
+        let value = msg.value().unwrap();
+
+        assert_eq!(
+            value.get_path(&[Index::Key("b.B"), Index::Key("Prop"), Index::Field(1)]),
+            Some(&ValueView::String("u"))
+        );
+        assert_eq!(
+            value.get_path(&[Index::Field(0), Index::Field(0)]),
+            Some(&ValueView::String("a.A"))
+        );
+        assert_eq!(value.get_path(&[]), Some(&value));
+
+        assert_eq!(value.get_path(&[Index::Key("c.C")]), None);
+        assert_eq!(value.get_path(&[Index::Field(2)]), None);
+        assert_eq!(value.get_path(&[Index::Field(0), Index::Key("a.A")]), None);
+    }
+
+    #[test]
+    fn test_fields() {
+        let tuple = ValueView::Tuple(vec![ValueView::Int32(42), ValueView::String("hi")]);
+
+        assert_eq!(tuple.field_as::<i32>(0), Ok(42));
+        assert_eq!(tuple.field_as::<&str>(1), Ok("hi"));
+        assert_eq!(tuple.field_as::<i32>(1), Err(Error::ValueTypeMismatch));
+        assert_eq!(tuple.field_as::<i32>(2), Err(Error::ValueTypeMismatch));
+
+        let pair = ValueView::Pair(Box::new(ValueView::Bool(true)), Box::new(ValueView::Unit));
+
+        assert_eq!(pair.field_as::<bool>(0), Ok(true));
+        assert!(matches!(pair.field(1), Some(ValueView::Unit)));
+        assert!(pair.field(2).is_none());
+
+        assert!(ValueView::Int32(42).field(0).is_none());
+    }
+
+    #[test]
+    fn test_checked_slice_lengths() {
+        let data = [1u8, 2, 3];
+
+        unsafe {
+            assert_eq!(checked_slice(data.as_ptr(), data.len()), Ok(&data[..]));
+            assert_eq!(checked_slice(ptr::null(), 0), Ok(&[][..]));
+            assert_eq!(checked_slice(ptr::null(), 3), Err(Error::InvalidData));
+
+            // lengths past isize::MAX can't be turned into slices, whatever the pointer
+            assert_eq!(
+                checked_slice(data.as_ptr(), usize::MAX),
+                Err(Error::Overflow)
+            );
+            assert_eq!(
+                checked_slice(data.as_ptr(), isize::MAX as usize + 1),
+                Err(Error::Overflow)
+            );
+        }
+    }
+}
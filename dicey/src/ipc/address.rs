@@ -16,13 +16,26 @@
 
 use std::{
     ffi::{CStr, CString},
-    mem, path,
+    fmt,
+    mem::{self, ManuallyDrop},
+    path,
 };
 
 use dicey_sys::{dicey_addr, dicey_addr_deinit, dicey_addr_from_str};
 
+// Addresses are either Unix domain sockets or Windows named pipes: libdicey has no TCP transport, and none of the
+// options that come with one (keepalive, buffer sizes) to set at connect time.
+// Addresses are always dialed. libdicey can adopt an already open descriptor (dicey_client_open_fd), but only uses it
+// internally for plugins and doesn't export it, so it isn't available when linking against a shared libdicey, and
+// callers can't configure a socket themselves before handing it over either.
+// Likewise, the connected socket is never handed out, so there's no descriptor to query peer credentials (e.g.
+// SO_PEERCRED) on; that is something for servers to check at accept time, and these bindings only cover clients.
 pub struct Address {
     caddr: dicey_addr,
+
+    // libdicey only retains the fixed-up form of the address (e.g. abstract sockets start with a NUL), so we keep the
+    // string the address was created from around
+    text: CString,
 }
 
 impl Address {
@@ -33,15 +46,38 @@ impl Address {
             .into()
     }
 
+    pub fn as_str(&self) -> Option<&str> {
+        self.text.to_str().ok()
+    }
+
+    pub fn as_c_str(&self) -> &CStr {
+        &self.text
+    }
+
+    // what libdicey will make of the address when connecting
+    pub fn transport(&self) -> Transport {
+        if cfg!(windows) {
+            Transport::NamedPipe
+        } else if cfg!(target_os = "linux") && self.text.to_bytes().starts_with(b"@") {
+            Transport::AbstractSocket
+        } else {
+            Transport::UnixSocket
+        }
+    }
+
     pub(crate) fn into_raw(self) -> dicey_addr {
-        let caddr = self.caddr;
+        let mut this = ManuallyDrop::new(self);
 
-        mem::forget(self);
+        drop(mem::take(&mut this.text));
 
-        caddr
+        this.caddr
     }
 }
 
+// the address exclusively owns the buffer its dicey_addr points to
+unsafe impl Send for Address {}
+unsafe impl Sync for Address {}
+
 impl Drop for Address {
     fn drop(&mut self) {
         unsafe {
@@ -79,6 +115,76 @@ impl From<&CStr> for Address {
             caddr
         };
 
-        Self { caddr }
+        Self {
+            caddr,
+            text: addr.to_owned(),
+        }
+    }
+}
+
+impl fmt::Debug for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Address").field(&self.text).finish()
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text.to_string_lossy())
+    }
+}
+
+impl PartialEq for Address {
+    fn eq(&self, other: &Self) -> bool {
+        self.text == other.text
+    }
+}
+
+impl Eq for Address {}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Transport {
+    // a Linux abstract socket, given as its name prefixed by '@'
+    AbstractSocket,
+
+    // a Windows named pipe, e.g. \\.\pipe\name
+    NamedPipe,
+
+    // a Unix domain socket, given as its path
+    UnixSocket,
+}
+
+/// Lists the kinds of addresses the linked libdicey can connect to.
+///
+/// libdicey has no way to query this, but its transports depend on the platform alone: Unix domain sockets everywhere
+/// but Windows, which has named pipes instead, plus abstract sockets on Linux. The list is derived from the target
+/// these bindings are built for, and is the same for every build of libdicey on it.
+pub const fn supported_transports() -> &'static [Transport] {
+    if cfg!(windows) {
+        &[Transport::NamedPipe]
+    } else if cfg!(target_os = "linux") {
+        &[Transport::UnixSocket, Transport::AbstractSocket]
+    } else {
+        &[Transport::UnixSocket]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transport() {
+        let addr = Address::from("/tmp/dicey.sock");
+
+        assert!(supported_transports().contains(&addr.transport()));
+
+        if cfg!(target_os = "linux") {
+            assert_eq!(addr.transport(), Transport::UnixSocket);
+            assert_eq!(
+                Address::from("@dicey").transport(),
+                Transport::AbstractSocket
+            );
+        }
     }
 }
@@ -15,52 +15,166 @@
  */
 
 use std::{
-    ffi::{CStr, CString, c_char},
+    ffi::{CStr, c_char},
     mem,
     os::raw::c_void,
     pin::Pin,
-    ptr,
+    ptr, slice,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
 };
 
 use dicey_sys::{
     DICEY_INTROSPECTION_DATA_PROP_NAME, DICEY_INTROSPECTION_TRAIT_NAME,
     DICEY_INTROSPECTION_XML_PROP_NAME, dicey_client, dicey_client_args, dicey_client_connect,
-    dicey_client_delete, dicey_client_disconnect, dicey_client_get_context,
+    dicey_client_delete, dicey_client_disconnect, dicey_client_event,
+    dicey_client_event_type_DICEY_CLIENT_EVENT_MESSAGE_RECEIVING, dicey_client_get_context,
     dicey_client_is_running, dicey_client_new, dicey_client_request, dicey_client_set_context,
     dicey_client_subscribe_result, dicey_client_subscribe_result_deinit, dicey_client_subscribe_to,
-    dicey_client_unsubscribe_from, dicey_error, dicey_error_DICEY_OK, dicey_packet,
-    dicey_packet_is_valid, dicey_selector,
+    dicey_client_unsubscribe_from, dicey_error_DICEY_OK, dicey_packet, dicey_packet_is_valid,
+    dicey_selector,
 };
 
 use crate::{
-    Error, Message, MessageBuilder, ObjectInfo, Op, Selector, ToDicey, ValueBuilder, ValueView,
+    DiceyError, Error, Message, MessageBuilder, ObjectInfo, Op, Selector, ToDicey, ValueBuilder,
+    check_abi,
     core::{
         macros::ccall,
-        value::{FromDicey, PathBuf, bytes_to_cpath},
+        value::{FromDicey, PathBuf, bytes_to_cpath, bytes_to_cstring},
     },
 };
 
-use super::{DEFAULT_TIMEOUT_MS, address::Address};
+use super::{
+    DEFAULT_TIMEOUT_MS, NO_TIMEOUT, address::Address, collect_subscriptions,
+    object_info::parse_trait_names,
+};
+
+pub use super::{Direction, Inspector};
 
-pub trait EventHandler: FnMut(Message) + Send + Sync {}
+use uuid::Uuid;
 
-impl<F: FnMut(Message) + Send + Sync> EventHandler for F {}
+pub trait EventHandler<C = ()>: FnMut(&C, Message) + Send + Sync {}
 
-pub struct ClientArgs<A: Into<Address>, F: EventHandler> {
+impl<C, F: FnMut(&C, Message) + Send + Sync> EventHandler<C> for F {}
+
+pub struct ClientArgs<A: Into<Address>, F: EventHandler<C>, C = ()> {
     pub pipe: A,
     pub on_event: Option<F>,
+
+    // handed to on_event and to the added event handlers together with each event, and dropped with the client
+    pub context: C,
+
+    // sees the raw bytes of every message going through the client, for protocol debugging
+    pub inspect: Option<Inspector>,
+}
+
+// a builder-style alternative to writing out every field, as in `ClientArgs::new(pipe).context(ctx).on_event(f)`
+impl<A: Into<Address>> ClientArgs<A, fn(&(), Message)> {
+    pub fn new(pipe: A) -> Self {
+        Self {
+            pipe,
+            on_event: None,
+            context: (),
+            inspect: None,
+        }
+    }
+}
+
+impl<A: Into<Address>, C> ClientArgs<A, fn(&C, Message), C> {
+    // the context decides what the event handler takes, so it must be set first, before there is a handler
+    pub fn context<D>(self, context: D) -> ClientArgs<A, fn(&D, Message), D> {
+        ClientArgs {
+            pipe: self.pipe,
+            on_event: None,
+            context,
+            inspect: self.inspect,
+        }
+    }
+}
+
+impl<A: Into<Address>, F: EventHandler<C>, C> ClientArgs<A, F, C> {
+    pub fn on_event<G: EventHandler<C>>(self, on_event: G) -> ClientArgs<A, G, C> {
+        ClientArgs {
+            pipe: self.pipe,
+            on_event: Some(on_event),
+            context: self.context,
+            inspect: self.inspect,
+        }
+    }
+
+    pub fn inspect(self, inspect: impl FnMut(Direction, &[u8]) + Send + 'static) -> Self {
+        Self {
+            inspect: Some(Box::new(inspect)),
+            ..self
+        }
+    }
 }
 
-pub struct Client<'a> {
-    state: Pin<Box<ClientState<'a>>>,
+/// A client that blocks the calling thread until each request gets its reply.
+///
+/// A client can be moved to and shared between threads: libdicey queues requests coming from any thread into the
+/// client's own event loop, which is the only thread running the event handlers. That is also why the handlers must be
+/// `Send`, and must never make requests through the client delivering them, as the loop would end up waiting on itself.
+///
+/// ```no_run
+/// use std::{sync::Arc, thread};
+///
+/// use dicey::blocking::{Client, ClientArgs};
+///
+/// let client = Arc::new(
+///     Client::connect(ClientArgs::new("/tmp/dicey.sock").on_event(|_: &(), msg| println!("{msg:?}")))
+///         .unwrap(),
+/// );
+///
+/// let worker = thread::spawn({
+///     let client = client.clone();
+///
+///     move || client.get("/foo", ("a.Trait", "Prop")).map(drop)
+/// });
+///
+/// worker.join().unwrap().unwrap();
+/// ```
+///
+/// ```compile_fail
+/// use std::rc::Rc;
+///
+/// use dicey::blocking::{Client, ClientArgs};
+///
+/// let shared = Rc::new(());
+///
+/// let _ = Client::connect(ClientArgs {
+///     pipe: "/tmp/dicey.sock",
+///     on_event: Some(move |_: &(), _| drop(shared.clone())),
+///     context: (),
+///     inspect: None,
+/// });
+/// ```
+pub struct Client<'a, C = ()> {
+    state: Pin<Box<ClientState<'a, C>>>,
 }
 
-impl<'a> Client<'a> {
-    pub fn connect<A, F>(ClientArgs { pipe, on_event }: ClientArgs<A, F>) -> Result<Self, Error>
+// identifies a handler added with Client::add_event_handler
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct HandlerId(u64);
+
+impl<'a, C> Client<'a, C> {
+    pub fn connect<A, F>(
+        ClientArgs {
+            pipe,
+            on_event,
+            context,
+            inspect,
+        }: ClientArgs<A, F, C>,
+    ) -> Result<Self, Error>
     where
         A: Into<Address>,
-        F: EventHandler + 'a,
+        F: EventHandler<C> + 'a,
+        C: Send + Sync + 'a,
     {
+        check_abi()?;
+
         let addr = pipe.into();
 
         let ptr = unsafe {
@@ -70,8 +184,12 @@ impl<'a> Client<'a> {
                 client_new,
                 &mut cln,
                 &dicey_client_args {
-                    inspect_func: None,
-                    on_signal: Some(client_on_event),
+                    inspect_func: if inspect.is_some() {
+                        Some(client_on_inspect::<C>)
+                    } else {
+                        None
+                    },
+                    on_signal: Some(client_on_event::<C>),
                 }
             )?;
 
@@ -79,15 +197,27 @@ impl<'a> Client<'a> {
         };
 
         // ensure that Drop will run if something goes wrong
-        let mut client = Self {
+        let client = Self {
             state: Box::pin(ClientState {
                 ptr,
-                on_event: on_event.map(|f| Box::new(f) as Box<dyn FnMut(Message)>),
+                on_event: on_event.map(|f| {
+                    let f = Mutex::new(f);
+
+                    Box::new(move |context: &C, msg| {
+                        if let Ok(mut f) = f.lock() {
+                            f(context, msg);
+                        }
+                    }) as SharedEventCallback<C>
+                }),
+                context,
+                handlers: Mutex::new(Vec::new()),
+                next_handler: AtomicU64::new(0),
+                inspect: inspect.map(Mutex::new),
             }),
         };
 
         unsafe {
-            dicey_client_set_context(ptr, &mut *client.state as *mut _ as *mut c_void);
+            dicey_client_set_context(ptr, &*client.state as *const _ as *mut c_void);
 
             ccall!(client_connect, client.ptr(), addr.into_raw())?;
         }
@@ -95,19 +225,113 @@ impl<'a> Client<'a> {
         Ok(client)
     }
 
+    /// Adds a handler that receives every event alongside `on_event` and the other added handlers, each getting its own
+    /// copy of the message.
+    ///
+    /// Handlers run on the client's event loop, and must not add or remove handlers themselves. Unlike `on_event`, they
+    /// can't borrow from their surroundings.
+    pub fn add_event_handler(&self, f: impl EventHandler<C> + 'static) -> HandlerId {
+        let id = HandlerId(self.state.next_handler.fetch_add(1, Ordering::Relaxed));
+
+        self.state
+            .handlers
+            .lock()
+            .expect("event handlers poisoned")
+            .push((id, Box::new(f)));
+
+        id
+    }
+
+    /// Sends an event to the server as if this client were the one emitting it.
+    ///
+    /// This only works with servers that explicitly accept events originating from clients; others will reject the
+    /// message. The server's reply is returned as-is.
+    pub fn emit_event<'b>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+        value: impl ToDicey,
+    ) -> Result<Message, Error> {
+        let reply = self
+            .request(Op::Event)
+            .path(path)?
+            .selector(selector)?
+            .value(value)?
+            .submit()?;
+
+        match reply.op() {
+            Op::Response => Ok(reply),
+            _ => Err(Error::InvalidData),
+        }
+    }
+
     pub fn exec<'b>(
         &self,
         path: impl Into<Vec<u8>>,
         selector: impl Into<Selector<'b>>,
         argument: impl ToDicey,
+    ) -> Result<Message, Error> {
+        self.exec_with(path, selector, |builder| builder.set(argument))
+    }
+
+    // execs an operation and extracts its result as T, turning error replies into errors. The view itself can't be
+    // returned, as it borrows from the reply
+    pub fn call<'b, T>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+        args: impl ToDicey,
+    ) -> Result<T, Error>
+    where
+        T: for<'v> FromDicey<'v>,
+    {
+        self.exec(path, selector, args)?.into_typed()
+    }
+
+    pub fn exec_with<'b, F>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+        op: F,
+    ) -> Result<Message, Error>
+    where
+        F: FnOnce(&mut ValueBuilder) -> Result<(), Error>,
+    {
+        self.request(Op::Exec)
+            .path(path)?
+            .selector(selector)?
+            .value_with(op)?
+            .submit()
+    }
+
+    /// Like [`Client::set_raw`], but for operations: returns the server's reply as-is, even when it carries an error
+    /// value.
+    pub fn exec_raw<'b>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+        argument: impl ToDicey,
     ) -> Result<Message, Error> {
         self.request(Op::Exec)
             .path(path)?
             .selector(selector)?
             .value(argument)?
-            .submit()
+            .submit_raw()
+    }
+
+    /// Does nothing, as there's never anything left to flush.
+    ///
+    /// Every request made by a blocking client, events included, waits for the server's reply before returning, so by
+    /// then libdicey has already written the whole request to the socket.
+    pub fn flush(&self) -> Result<(), Error> {
+        Ok(())
     }
 
+    /// Reads a single property.
+    ///
+    /// The dicey protocol has no selector-less Get: every message targets one element of one trait, and libdicey
+    /// refuses to build a Get without a selector. Servers that want to offer whole-object reads do so by exposing a
+    /// property aggregating the object's state, which is read like any other.
     pub fn get<'b>(
         &self,
         path: impl Into<Vec<u8>>,
@@ -120,47 +344,86 @@ impl<'a> Client<'a> {
     }
 
     pub fn inspect(&self, path: impl Into<Vec<u8>>) -> Result<ObjectInfo, Error> {
-        let path = path.into();
-        let path_str = String::from_utf8(path.clone()).map_err(|_| Error::InvalidData)?;
+        self.inspect_timeout(path, DEFAULT_TIMEOUT_MS)
+    }
 
-        self.get(
+    pub fn inspect_timeout(
+        &self,
+        path: impl Into<Vec<u8>>,
+        timeout_ms: u32,
+    ) -> Result<ObjectInfo, Error> {
+        self.inspect_with(
             path,
             (
                 DICEY_INTROSPECTION_TRAIT_NAME.as_ref(),
                 DICEY_INTROSPECTION_DATA_PROP_NAME.as_ref(),
             ),
+            timeout_ms,
         )
-        .and_then(move |m| match m.value() {
-            Some(ValueView::Error(e)) => Err(Error::from(e.code as dicey_error)),
-            Some(ref view) => ObjectInfo::from_dicey(path_str, view),
-            _ => Err(Error::BadMessage),
-        })
+    }
+
+    pub fn inspect_via<'b>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+    ) -> Result<ObjectInfo, Error> {
+        self.inspect_with(path, selector, DEFAULT_TIMEOUT_MS)
     }
 
     pub fn inspect_as_xml(&self, path: impl Into<Vec<u8>>) -> Result<String, Error> {
-        self.get(
-            path,
-            (
+        self.inspect_as_xml_timeout(path, DEFAULT_TIMEOUT_MS)
+    }
+
+    pub fn inspect_as_xml_timeout(
+        &self,
+        path: impl Into<Vec<u8>>,
+        timeout_ms: u32,
+    ) -> Result<String, Error> {
+        self.request(Op::Get)
+            .path(path)?
+            .selector((
                 DICEY_INTROSPECTION_TRAIT_NAME.as_ref(),
                 DICEY_INTROSPECTION_XML_PROP_NAME.as_ref(),
-            ),
-        )
-        .and_then(|m| match m.value() {
-            Some(ValueView::String(s)) => Ok(s.to_owned()),
-            Some(ValueView::Error(e)) => Err(Error::from(e.code as dicey_error)),
-            _ => Err(Error::BadMessage),
-        })
+            ))?
+            .timeout(timeout_ms)
+            .submit()?
+            .into_typed()
+    }
+
+    pub fn is_property_writable<'b>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+    ) -> Result<bool, Error> {
+        let sel = selector.into();
+
+        self.inspect(path)?.is_property_writable(sel)
     }
 
     pub fn is_running(&self) -> bool {
         !self.ptr().is_null() && unsafe { dicey_client_is_running(self.ptr()) }
     }
 
-    pub fn request(&self, op: Op) -> RequestBuilder<'_> {
+    // returns false if there's no such handler, e.g. because it was already removed
+    pub fn remove_event_handler(&self, id: HandlerId) -> bool {
+        let mut handlers = self.state.handlers.lock().expect("event handlers poisoned");
+
+        let len = handlers.len();
+
+        handlers.retain(|(handler, _)| *handler != id);
+
+        handlers.len() != len
+    }
+
+    pub fn request(&self, op: Op) -> RequestBuilder<'_, C> {
         RequestBuilder::new(self, op)
     }
 
     pub fn request_with(&self, msg: Message, timeout_ms: u32) -> Result<Message, Error> {
+        if let Some(inspect) = &self.state.inspect {
+            inspect.lock().expect("inspector poisoned")(Direction::Outbound, msg.raw_bytes());
+        }
+
         unsafe {
             let mut c_resp = mem::zeroed();
 
@@ -182,22 +445,61 @@ impl<'a> Client<'a> {
         selector: impl Into<Selector<'b>>,
         argument: impl ToDicey,
     ) -> Result<(), Error> {
+        self.set_with(path, selector, |builder| builder.set(argument))
+    }
+
+    pub fn set_with<'b, F>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+        op: F,
+    ) -> Result<(), Error>
+    where
+        F: FnOnce(&mut ValueBuilder) -> Result<(), Error>,
+    {
         let msg = self
             .request(Op::Set)
             .path(path)?
             .selector(selector)?
-            .value(argument)?
+            .value_with(op)?
             .submit()?;
 
-        debug_assert!(
-            msg.value()
-                .and_then(|ref v| <()>::from_dicey(v).ok())
-                .is_some()
-        );
+        let value = msg.reply_value()?;
+        debug_assert!(<()>::from_dicey(&value).is_ok());
 
         Ok(())
     }
 
+    /// Like [`Client::set`], but returns the server's reply as-is, even when it carries an error value.
+    pub fn set_raw<'b>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+        argument: impl ToDicey,
+    ) -> Result<Message, Error> {
+        self.request(Op::Set)
+            .path(path)?
+            .selector(selector)?
+            .value(argument)?
+            .submit()
+    }
+
+    pub fn set_strict<'b>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+        argument: impl ToDicey,
+    ) -> Result<(), Error> {
+        let path = path.into();
+        let sel = selector.into();
+
+        if !self.is_property_writable(path.clone(), sel)? {
+            return Err(Error::InvalidData);
+        }
+
+        self.set(path, sel, argument)
+    }
+
     pub fn subscribe_to<'b>(
         &self,
         path: impl Into<Vec<u8>>,
@@ -207,8 +509,8 @@ impl<'a> Client<'a> {
 
         let sel = selector.into();
 
-        let tname = Some(CString::new(sel.trait_name).map_err(|_| Error::InvalidData)?);
-        let elem = Some(CString::new(sel.elem).map_err(|_| Error::InvalidData)?);
+        let tname = Some(bytes_to_cstring(sel.trait_name)?);
+        let elem = Some(bytes_to_cstring(sel.elem)?);
 
         let csel = dicey_selector {
             trait_: tname.as_ref().unwrap().as_ptr() as *const c_char,
@@ -240,6 +542,32 @@ impl<'a> Client<'a> {
         }
     }
 
+    // every subscription is attempted even after one fails; on failure the error carries a PartialSubscription telling
+    // which ones went through and must be unsubscribed to roll back
+    pub fn subscribe_many<'b, P: AsRef<str>>(
+        &self,
+        subs: &[(P, Selector<'b>)],
+    ) -> Result<Vec<Option<PathBuf>>, DiceyError> {
+        let results = subs
+            .iter()
+            .map(|(path, sel)| self.subscribe_to(path.as_ref(), *sel))
+            .collect();
+
+        collect_subscriptions(results)
+    }
+
+    /// Lists the names of the traits implemented by the object at `path`, without decoding their elements.
+    pub fn traits(&self, path: impl Into<Vec<u8>>) -> Result<Vec<String>, Error> {
+        self.get(
+            path,
+            (
+                DICEY_INTROSPECTION_TRAIT_NAME.as_ref(),
+                DICEY_INTROSPECTION_DATA_PROP_NAME.as_ref(),
+            ),
+        )
+        .and_then(|m| parse_trait_names(&m))
+    }
+
     pub fn unsubscribe_from<'b>(
         &self,
         path: impl Into<Vec<u8>>,
@@ -249,8 +577,8 @@ impl<'a> Client<'a> {
 
         let sel = selector.into();
 
-        let tname = Some(CString::new(sel.trait_name).map_err(|_| Error::InvalidData)?);
-        let elem = Some(CString::new(sel.elem).map_err(|_| Error::InvalidData)?);
+        let tname = Some(bytes_to_cstring(sel.trait_name)?);
+        let elem = Some(bytes_to_cstring(sel.elem)?);
 
         let csel = dicey_selector {
             trait_: tname.as_ref().unwrap().as_ptr() as *const c_char,
@@ -269,12 +597,41 @@ impl<'a> Client<'a> {
         .map(|_| ())
     }
 
+    /// Checks that the client can take requests.
+    ///
+    /// `connect` only returns after the handshake has completed, so there is nothing to wait for: this fails with
+    /// [`Error::ConnectionReset`] if the connection has been lost since, given that the client doesn't reconnect.
+    pub fn wait_ready(&self) -> Result<(), Error> {
+        if self.is_running() {
+            Ok(())
+        } else {
+            Err(Error::ConnectionReset)
+        }
+    }
+
+    fn inspect_with<'b>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+        timeout_ms: u32,
+    ) -> Result<ObjectInfo, Error> {
+        let path = path.into();
+        let path_str = String::from_utf8(path.clone()).map_err(|_| Error::InvalidData)?;
+
+        self.request(Op::Get)
+            .path(path)?
+            .selector(selector)?
+            .timeout(timeout_ms)
+            .submit()
+            .and_then(move |m| ObjectInfo::from_dicey(path_str, &m.reply_value()?))
+    }
+
     fn ptr(&self) -> *mut dicey_client {
         self.state.ptr
     }
 }
 
-impl Drop for Client<'_> {
+impl<C> Drop for Client<'_, C> {
     fn drop(&mut self) {
         unsafe {
             //attempt disconnecting. We don't really care about the result.
@@ -285,21 +642,58 @@ impl Drop for Client<'_> {
     }
 }
 
-pub struct RequestBuilder<'a> {
-    client: &'a Client<'a>,
+pub struct RequestBuilder<'a, C = ()> {
+    client: &'a Client<'a, C>,
 
     mbuilder: MessageBuilder,
     timeout_ms: u32,
+    idempotency_key: Option<Uuid>,
+    has_value: bool,
 }
 
-impl<'a> RequestBuilder<'a> {
-    fn new(client: &'a Client, op: Op) -> Self {
+impl<'a, C> RequestBuilder<'a, C> {
+    fn new(client: &'a Client<'a, C>, op: Op) -> Self {
         Self {
             client,
             mbuilder: MessageBuilder::new(op)
                 .expect("failed to create message builder (out of memory?)"),
             timeout_ms: DEFAULT_TIMEOUT_MS,
+            idempotency_key: None,
+            has_value: false,
+        }
+    }
+
+    pub fn current_selector(&self) -> Option<Selector<'_>> {
+        self.mbuilder.current_selector()
+    }
+
+    pub const fn current_seq(&self) -> Option<u32> {
+        self.mbuilder.current_seq()
+    }
+
+    /// Tags the request with a key servers can use to recognise repeated attempts of the same operation.
+    ///
+    /// The key is sent together with the value as a `{#v}` pair, so it must be set before the value and only makes sense
+    /// with servers that expect it. Setting it once the value is set fails with [`Error::InvalidData`].
+    pub fn idempotency_key(self, key: Uuid) -> Result<Self, Error> {
+        if self.has_value {
+            return Err(Error::InvalidData);
         }
+
+        Ok(Self {
+            idempotency_key: Some(key),
+            ..self
+        })
+    }
+
+    pub const fn op(&self) -> Op {
+        self.mbuilder.op()
+    }
+
+    /// Makes the request wait for a reply indefinitely, blocking the calling thread until the server answers or the
+    /// connection is closed.
+    pub fn no_timeout(self) -> Self {
+        self.timeout(NO_TIMEOUT)
     }
 
     pub fn path(self, path: impl Into<Vec<u8>>) -> Result<Self, Error> {
@@ -309,6 +703,10 @@ impl<'a> RequestBuilder<'a> {
         })
     }
 
+    pub fn path_str(&self) -> Option<&str> {
+        self.mbuilder.path_str()
+    }
+
     pub fn selector<'b>(self, sel: impl Into<Selector<'b>>) -> Result<Self, Error> {
         Ok(Self {
             mbuilder: self.mbuilder.selector(sel)?,
@@ -316,6 +714,17 @@ impl<'a> RequestBuilder<'a> {
         })
     }
 
+    pub fn selector_cstr(self, trait_name: &CStr, elem: &CStr) -> Result<Self, Error> {
+        Ok(Self {
+            mbuilder: self.mbuilder.selector_cstr(trait_name, elem)?,
+            ..self
+        })
+    }
+
+    /// Sets the sequence number of the request.
+    ///
+    /// Clients always assign their own unique sequence number when sending a request, overriding this one; the number
+    /// actually used can be read from the reply with [`Message::seq`].
     pub fn seq(self, seq: u32) -> Self {
         Self {
             mbuilder: self.mbuilder.seq(seq),
@@ -323,28 +732,53 @@ impl<'a> RequestBuilder<'a> {
         }
     }
 
+    // only timeouts are retried, given that they're the only errors a new attempt may fix. Exec requests are only retried
+    // when they carry an idempotency key, because a timed out Exec may still have been executed by the server
+    pub fn submit_with_retries(self, attempts: u32) -> Result<Message, Error> {
+        if self.mbuilder.op() == Op::Exec && self.idempotency_key.is_none() && attempts > 1 {
+            return Err(Error::InvalidData);
+        }
+
+        let msg = self.mbuilder.build()?;
+
+        for _ in 1..attempts {
+            match self.client.request_with(msg.clone(), self.timeout_ms) {
+                Err(Error::TimedOut) => continue,
+                res => return res,
+            }
+        }
+
+        self.client.request_with(msg, self.timeout_ms)
+    }
+
+    // the reply is returned as-is, error values included
     pub fn submit(self) -> Result<Message, Error> {
         self.client
             .request_with(self.mbuilder.build()?, self.timeout_ms)
     }
 
+    /// Sends the request and returns the reply verbatim, even when it carries an error value.
+    pub fn submit_raw(self) -> Result<Message, Error> {
+        self.submit()
+    }
+
     pub fn timeout(self, timeout_ms: u32) -> Self {
         Self { timeout_ms, ..self }
     }
 
     pub fn value(self, value: impl ToDicey) -> Result<Self, Error> {
-        Ok(Self {
-            mbuilder: self.mbuilder.value(value)?,
-            ..self
-        })
+        self.value_with(|builder| builder.set(value))
     }
 
     pub fn value_with<F>(self, op: F) -> Result<Self, Error>
     where
         F: FnOnce(&mut ValueBuilder) -> Result<(), Error>,
     {
+        let mbuilder = self.mbuilder.value_with_key(self.idempotency_key, op)?;
+
         Ok(Self {
-            mbuilder: self.mbuilder.value_with(op)?,
+            mbuilder,
+            has_value: true,
             ..self
         })
     }
@@ -352,13 +786,58 @@ impl<'a> RequestBuilder<'a> {
 
 // we must put the client internal state in a separate struct we then allocate into the heap,
 // otherwise we can't really pin it to a specific memory location
-struct ClientState<'a> {
+struct ClientState<'a, C> {
     ptr: *mut dicey_client,
 
-    on_event: Option<Box<dyn FnMut(Message) + 'a>>,
+    // called through a shared reference, like everything else in the state, so the callback carries its own lock.
+    // Keeping the lock inside the box rather than around it keeps the client covariant over 'a
+    on_event: Option<SharedEventCallback<'a, C>>,
+    context: C,
+
+    // added after connecting, from any thread, so the list itself needs a lock. They are 'static so that the lock
+    // doesn't make the client invariant over 'a
+    handlers: Mutex<Vec<(HandlerId, EventCallback<C>)>>,
+    next_handler: AtomicU64,
+
+    // outbound packets are seen from the sending thread, inbound ones from the event loop
+    inspect: Option<Mutex<Inspector>>,
 }
 
-unsafe extern "C" fn client_on_event(
+type EventCallback<C> = Box<dyn FnMut(&C, Message) + Send>;
+type SharedEventCallback<'a, C> = Box<dyn Fn(&C, Message) + Send + 'a>;
+
+// requests from any thread are queued into the client's own loop; the state is either immutable or behind a lock
+unsafe impl<C: Send + Sync> Send for ClientState<'_, C> {}
+unsafe impl<C: Send + Sync> Sync for ClientState<'_, C> {}
+
+unsafe extern "C" fn client_on_inspect<C>(
+    _: *mut dicey_client,
+    ctx: *mut c_void,
+    event: dicey_client_event,
+) {
+    // events raised by dicey_client_new come before the context is set. libdicey never raises MESSAGE_SENDING, which
+    // is why outbound packets are reported by request_with instead
+    if ctx.is_null() || event.type_ != dicey_client_event_type_DICEY_CLIENT_EVENT_MESSAGE_RECEIVING
+    {
+        return;
+    }
+
+    let state = unsafe { &*(ctx as *const ClientState<C>) };
+
+    if let Some(inspect) = &state.inspect
+        && let Ok(mut inspect) = inspect.lock()
+    {
+        let packet = unsafe { event.__bindgen_anon_1.packet };
+
+        if !packet.payload.is_null() {
+            inspect(Direction::Inbound, unsafe {
+                slice::from_raw_parts(packet.payload as *const u8, packet.nbytes)
+            });
+        }
+    }
+}
+
+unsafe extern "C" fn client_on_event<C>(
     c_client: *mut dicey_client,
     ctx: *mut ::std::os::raw::c_void,
     packet: *mut dicey_packet,
@@ -366,13 +845,164 @@ unsafe extern "C" fn client_on_event(
     let state = unsafe {
         assert!(!c_client.is_null() && !ctx.is_null() && dicey_packet_is_valid(*packet));
 
-        &mut *(dicey_client_get_context(c_client) as *mut ClientState)
+        // other threads may be holding shared references to the state, so only take a shared one
+        &*(dicey_client_get_context(c_client) as *const ClientState<C>)
     };
 
-    if let Some(cb) = state.on_event.as_mut() {
-        let message = Message::from_raw(unsafe { ptr::replace(packet, mem::zeroed()) })
-            .expect("failed to convert packet to message");
+    let mut handlers = state.handlers.lock().expect("event handlers poisoned");
+
+    if state.on_event.is_none() && handlers.is_empty() {
+        return;
+    }
+
+    let message = Message::from_raw(unsafe { ptr::replace(packet, mem::zeroed()) })
+        .expect("failed to convert packet to message");
 
-        cb(message);
+    for (_, handler) in handlers.iter_mut() {
+        handler(&state.context, message.clone());
+    }
+
+    drop(handlers);
+
+    if let Some(cb) = &state.on_event {
+        cb(&state.context, message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thread_safety() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<Client<'_>>();
+        assert_send_sync::<Message>();
+        assert_send_sync::<ObjectInfo>();
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_event_context() {
+        use std::{sync::mpsc, time::Duration};
+
+        use crate::{
+            MessageBuilder,
+            testing::{SIGNAL_ELEMENT, SIGNAL_PATHS, SIGNAL_TRAIT, TestServer},
+        };
+
+        let server = TestServer::start().unwrap();
+
+        let (tx, rx) = mpsc::channel();
+
+        let client = Client::connect(ClientArgs::new(server.address()).context(7u32).on_event({
+            let tx = Mutex::new(tx.clone());
+
+            move |ctx: &u32, msg: Message| {
+                let _ = tx
+                    .lock()
+                    .unwrap()
+                    .send(("on_event", *ctx, msg.into_typed::<u32>()));
+            }
+        }))
+        .unwrap();
+
+        client.wait_ready().unwrap();
+
+        client.add_event_handler({
+            let tx = Mutex::new(tx);
+
+            move |ctx: &u32, msg: Message| {
+                let _ = tx
+                    .lock()
+                    .unwrap()
+                    .send(("handler", *ctx, msg.into_typed::<u32>()));
+            }
+        });
+
+        client
+            .subscribe_to(SIGNAL_PATHS[0], (SIGNAL_TRAIT, SIGNAL_ELEMENT))
+            .unwrap();
+
+        server
+            .raise(
+                MessageBuilder::event()
+                    .unwrap()
+                    .path(SIGNAL_PATHS[0])
+                    .unwrap()
+                    .selector((SIGNAL_TRAIT, SIGNAL_ELEMENT))
+                    .unwrap()
+                    .value(42u32)
+                    .unwrap()
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap();
+
+        let mut seen = (0..2)
+            .map(|_| rx.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect::<Vec<_>>();
+        seen.sort_by_key(|(who, _, _)| *who);
+
+        assert_eq!(seen, [("handler", 7, Ok(42)), ("on_event", 7, Ok(42))]);
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_traits() {
+        use crate::testing::{ECHO_PATH, ECHO_TRAIT, TestServer};
+
+        let server = TestServer::start().unwrap();
+
+        let client = Client::connect(ClientArgs::new(server.address())).unwrap();
+
+        client.wait_ready().unwrap();
+
+        assert!(
+            client
+                .traits(ECHO_PATH)
+                .unwrap()
+                .iter()
+                .any(|tname| tname == ECHO_TRAIT)
+        );
+
+        assert_eq!(
+            client.traits("/dicey/test/missing").err(),
+            Some(Error::PathNotFound)
+        );
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_subscribe_nul() {
+        use crate::testing::{SIGNAL_ELEMENT, SIGNAL_PATHS, SIGNAL_TRAIT, TestServer};
+
+        let server = TestServer::start().unwrap();
+
+        let client = Client::connect(ClientArgs::new(server.address())).unwrap();
+
+        client.wait_ready().unwrap();
+
+        // a single trailing NUL is the same as none
+        let path = format!("{}\0", SIGNAL_PATHS[1]);
+        let tname = format!("{SIGNAL_TRAIT}\0");
+        let elem = format!("{SIGNAL_ELEMENT}\0");
+
+        assert_eq!(
+            client.subscribe_to(SIGNAL_PATHS[0], (SIGNAL_TRAIT, SIGNAL_ELEMENT)),
+            Ok(None)
+        );
+        assert_eq!(
+            client.subscribe_to(path, (tname.as_str(), elem.as_str())),
+            Ok(None)
+        );
+
+        assert_eq!(
+            client
+                .subscribe_to(SIGNAL_PATHS[2], ("dicey.test\0.Signal", SIGNAL_ELEMENT))
+                .err(),
+            Some(Error::InvalidData)
+        );
     }
 }
@@ -15,40 +15,124 @@
  */
 
 use std::{
-    ffi::c_char,
-    mem::{self, ManuallyDrop},
+    ffi::{CStr, CString, c_char},
+    marker::PhantomData,
+    mem,
     os::raw::c_void,
-    pin::Pin,
-    ptr,
-    sync::Arc,
+    pin::{Pin, pin},
+    ptr, slice,
+    sync::{
+        Arc, Mutex, OnceLock, Weak,
+        atomic::{AtomicBool, Ordering},
+    },
+    task::{Context, Poll},
+    time::Duration,
 };
 
 use crate::{
-    Error, FromDicey, Message, MessageBuilder, ObjectInfo, Op, Selector, ToDicey, ValueBuilder,
-    ValueView,
-    core::{macros::ccall, value::Path},
+    DiceyError, Error, FromDicey, Message, MessageBuilder, ObjectInfo, Op, Packet, Selector,
+    SelectorBuf, ToDicey, ValueBuilder, ValueView, check_abi,
+    core::{
+        Version,
+        macros::ccall,
+        value::{Path, PathBuf, bytes_to_cpath, bytes_to_cstring},
+    },
 };
 
-use super::{DEFAULT_TIMEOUT_MS, address::Address};
+use super::{
+    DEFAULT_TIMEOUT_MS, Direction, Inspector, NO_TIMEOUT,
+    address::Address,
+    collect_subscriptions,
+    object_info::parse_trait_names,
+    stats::{ClientStats, StatsCounters},
+};
 
 use dicey_sys::{
     DICEY_EVENTMANAGER_SUBSCRIBE_OP_NAME, DICEY_EVENTMANAGER_TRAIT_NAME,
     DICEY_EVENTMANAGER_UNSUBSCRIBE_OP_NAME, DICEY_INTROSPECTION_DATA_PROP_NAME,
     DICEY_INTROSPECTION_TRAIT_NAME, DICEY_INTROSPECTION_XML_PROP_NAME, DICEY_SERVER_PATH,
     dicey_client, dicey_client_args, dicey_client_connect_async, dicey_client_delete,
-    dicey_client_disconnect, dicey_client_get_context, dicey_client_is_running, dicey_client_new,
-    dicey_client_request_async, dicey_client_set_context, dicey_error, dicey_packet,
-    dicey_packet_is_valid,
+    dicey_client_disconnect, dicey_client_event,
+    dicey_client_event_type_DICEY_CLIENT_EVENT_HANDSHAKE_START,
+    dicey_client_event_type_DICEY_CLIENT_EVENT_MESSAGE_RECEIVING, dicey_client_get_context,
+    dicey_client_is_running, dicey_client_new, dicey_client_request_async,
+    dicey_client_set_context, dicey_client_unsubscribe_from_async, dicey_error, dicey_packet,
+    dicey_packet_is_valid, dicey_selector,
 };
 
-use futures::channel::oneshot;
-use tokio::sync::broadcast::{Receiver, Sender};
+use futures::{
+    Stream, StreamExt,
+    channel::oneshot,
+    future::{self, Either, select},
+    stream::{self, BoxStream},
+};
+use tokio::sync::{
+    Notify,
+    broadcast::{
+        Receiver, Sender,
+        error::{RecvError, TryRecvError},
+    },
+    mpsc::{self, UnboundedReceiver, UnboundedSender},
+};
+use uuid::Uuid;
 
 pub const DEFAULT_EVENT_QUEUE_SIZE: usize = 32usize;
 
+// There's no way to name a client: the libdicey handshake only carries the protocol version, and servers have no
+// well-known property for clients to describe themselves through, so connections can only be told apart server-side by
+// how the server application tracks them.
 pub struct ClientArgs<A: Into<Address>> {
     pub pipe: A,
     pub event_queue_size: usize,
+    pub event_sink: EventSink,
+
+    // sees the raw bytes of every message going through the client, for protocol debugging
+    pub inspect: Option<Inspector>,
+}
+
+// a builder-style alternative to writing out every field, as in `ClientArgs::new(pipe).event_sink(EventSink::Unbounded)`
+impl<A: Into<Address>> ClientArgs<A> {
+    pub fn new(pipe: A) -> Self {
+        Self {
+            pipe,
+            event_queue_size: DEFAULT_EVENT_QUEUE_SIZE,
+            event_sink: EventSink::default(),
+            inspect: None,
+        }
+    }
+
+    pub fn event_queue_size(self, event_queue_size: usize) -> Self {
+        Self {
+            event_queue_size,
+            ..self
+        }
+    }
+
+    pub fn event_sink(self, event_sink: EventSink) -> Self {
+        Self { event_sink, ..self }
+    }
+
+    pub fn inspect(self, inspect: impl FnMut(Direction, &[u8]) + Send + 'static) -> Self {
+        Self {
+            inspect: Some(Box::new(inspect)),
+            ..self
+        }
+    }
+}
+
+/// How the events received by a client are queued for its [`SignalSource`]s.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum EventSink {
+    /// Events go through a shared queue of `event_queue_size` events. Sources that fall behind miss the oldest ones,
+    /// and fail with `Error::Overflow` when they do.
+    #[default]
+    Bounded,
+
+    /// Every source gets a queue of its own, which grows as needed and never loses events.
+    ///
+    /// The memory taken is unbounded: a source that stops being read keeps every event received since, until it's
+    /// dropped. Sources from [`Client::events_with_capacity`] stay bounded.
+    Unbounded,
 }
 
 pub struct Client {
@@ -57,95 +141,199 @@ pub struct Client {
 
 impl Client {
     pub async fn connect(pipe: impl Into<Address>) -> Result<Self, Error> {
-        Self::connect_with_args(ClientArgs {
-            pipe,
-            event_queue_size: DEFAULT_EVENT_QUEUE_SIZE,
-        })
-        .await
+        Self::connect_with_args(ClientArgs::new(pipe)).await
+    }
+
+    pub async fn connect_with_args<A: Into<Address>>(args: ClientArgs<A>) -> Result<Self, Error> {
+        Self::connect_future(args).await
     }
 
-    pub async fn connect_with_args<A: Into<Address>>(
+    /// Returns a future that connects to the server when first polled, for executors that need to store or spawn it.
+    ///
+    /// Dropping the future before it resolves (e.g. from `tokio::time::timeout`) blocks until libdicey is done with the
+    /// handshake, which can't be interrupted.
+    pub fn connect_future<A: Into<Address>>(
         ClientArgs {
             pipe,
             event_queue_size,
+            event_sink,
+            inspect,
         }: ClientArgs<A>,
-    ) -> Result<Self, Error> {
+    ) -> impl Future<Output = Result<Self, Error>> + Send + 'static {
         let addr = pipe.into();
 
-        let ptr = unsafe {
-            let mut cln = ptr::null_mut();
-
-            ccall!(
-                client_new,
-                &mut cln,
-                &dicey_client_args {
-                    inspect_func: None,
-                    on_signal: Some(client_on_event),
+        async move {
+            check_abi()?;
+
+            let (new_sized_events, added_sized_events) = mpsc::unbounded_channel();
+
+            // ensure that Drop will run if something goes wrong
+            let mut client = unsafe {
+                let mut cln = ptr::null_mut();
+
+                ccall!(
+                    client_new,
+                    &mut cln,
+                    &dicey_client_args {
+                        inspect_func: Some(client_on_inspect),
+                        on_signal: Some(client_on_event),
+                    }
+                )?;
+
+                Self {
+                    state: Box::pin(ClientState {
+                        ptr: cln,
+                        events: Sender::new(event_queue_size),
+                        event_queue_size,
+                        event_sink,
+                        new_sized_events,
+                        sized_events: Mutex::new(SizedQueues {
+                            queues: Vec::new(),
+                            added: added_sized_events,
+                        }),
+                        unbounded_events: Arc::new(Mutex::new(Vec::new())),
+                        handshake_version: OnceLock::new(),
+                        stats: StatsCounters::new(),
+                        inspect: inspect.map(Mutex::new),
+                        closing: AtomicBool::new(false),
+                        cancel: Notify::new(),
+                    }),
                 }
-            )?;
+            };
 
-            cln
-        };
+            type ConnectResult = Result<(), Error>;
+            type Tx = oneshot::Sender<ConnectResult>;
 
-        // ensure that Drop will run if something goes wrong
-        let mut client = Self {
-            state: Box::pin(ClientState {
-                ptr,
-                events: Sender::new(event_queue_size),
-            }),
-        };
+            extern "C" fn connect_cb(
+                client: *mut dicey_client,
+                ctx: *mut c_void,
+                status: dicey_error,
+                _: *const c_char,
+            ) {
+                debug_assert!(!client.is_null() && !ctx.is_null());
 
-        type ConnectResult = Result<(), Error>;
-        type Tx = oneshot::Sender<ConnectResult>;
+                let tx = unsafe { Box::from_raw(ctx as *mut Tx) };
 
-        extern "C" fn connect_cb(
-            client: *mut dicey_client,
-            ctx: *mut c_void,
-            status: dicey_error,
-            _: *const c_char,
-        ) {
-            debug_assert!(!client.is_null() && !ctx.is_null());
+                let status = Error::from(status);
 
-            let tx = unsafe { ManuallyDrop::into_inner(ptr::read(ctx as *mut ManuallyDrop<Tx>)) };
+                // the receiver is gone if the connect future was dropped before the handshake completed
+                let _ = tx.send(if status == Error::OK {
+                    Ok(())
+                } else {
+                    Err(status)
+                });
+            }
 
-            let status = Error::from(status);
+            let (tx, rx) = oneshot::channel::<ConnectResult>();
 
-            tx.send(if status == Error::OK {
-                Ok(())
-            } else {
-                Err(status)
-            })
-            .expect("receiver should never drop before here")
-        }
+            // the sender is moved into the heap, given that the callback may run after this future has been dropped
+            let tx = Box::into_raw(Box::new(tx));
 
-        let (tx, rx) = oneshot::channel::<ConnectResult>();
+            unsafe {
+                dicey_client_set_context(client.ptr(), &mut *client.state as *mut _ as *mut c_void);
 
-        let mut tx = ManuallyDrop::new(tx);
+                if let Err(err) = ccall!(
+                    client_connect_async,
+                    client.ptr(),
+                    addr.into_raw(),
+                    Some(connect_cb),
+                    tx as *mut c_void
+                ) {
+                    drop(Box::from_raw(tx));
 
-        unsafe {
-            dicey_client_set_context(ptr, &mut *client.state as *mut _ as *mut c_void);
+                    return Err(err);
+                }
+            }
 
-            if let Err(err) = ccall!(
-                client_connect_async,
-                client.ptr(),
-                addr.into_raw(),
-                Some(connect_cb),
-                &mut tx as *mut _ as *mut c_void
-            ) {
-                ManuallyDrop::drop(&mut tx);
+            let mut pending = PendingConnect {
+                client: Some(client),
+                rx,
+            };
 
-                return Err(err);
-            }
+            let res = (&mut pending.rx)
+                .await
+                .expect("sender should never drop before here");
+
+            res.map(|_| {
+                pending
+                    .client
+                    .take()
+                    .expect("client should only be taken once")
+            })
         }
+    }
+
+    /// Fails all pending requests with `Error::Cancelled`, without waiting for their replies or timeouts.
+    ///
+    /// The requests are still in flight as far as the server is concerned, and late replies are discarded. The client
+    /// never reconnects on its own, so nothing is retried: the cancelled requests stay failed, and the connection and
+    /// its subscriptions carry on as before. To start over after a lost connection, cancel and drop the client, then
+    /// connect a new one.
+    pub fn cancel_all(&self) {
+        self.state.cancel.notify_waiters();
+    }
 
-        rx.await
-            .expect("sender should never drop before here")
-            .map(|_| client)
+    // stops accepting requests and cancels the pending ones. The connection stays up until the client is dropped
+    pub fn close(&self) {
+        self.state.closing.store(true, Ordering::Release);
+
+        self.cancel_all();
     }
 
     pub fn events(&self) -> SignalSource {
         SignalSource {
-            signals: self.state.events.subscribe(),
+            signals: self.state.subscribe(),
+        }
+    }
+
+    // broadcast channels can't be resized in place, so sources that need more (or less) room than the shared queue get
+    // a queue of their own, which is fed every event alongside it and dropped with its last source
+    pub fn events_with_capacity(&self, capacity: usize) -> SignalSource {
+        let events = Sender::new(capacity);
+        let signals = events.subscribe();
+
+        self.state
+            .new_sized_events
+            .send(events)
+            .expect("the event loop's end of the queue should live as long as the client");
+
+        SignalSource {
+            signals: signals.into(),
+        }
+    }
+
+    pub fn event_queue_capacity(&self) -> usize {
+        self.state.event_queue_size
+    }
+
+    /// Returns how many events in the shared queue are still waiting to be read by the slowest [`SignalSource`].
+    ///
+    /// Once this reaches [`Client::event_queue_capacity`], new events overwrite the oldest ones and the sources that
+    /// missed them fail with `Error::Overflow`. With [`EventSink::Unbounded`] the shared queue is unused, and this is
+    /// always zero.
+    pub fn event_queue_len(&self) -> usize {
+        self.state.events.len()
+    }
+
+    /// Sends an event to the server as if this client were the one emitting it, with the same caveats as
+    /// [`blocking::Client::emit_event`](crate::blocking::Client::emit_event).
+    pub async fn emit_event<'b>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+        value: impl ToDicey,
+    ) -> Result<Message, Error> {
+        let reply = self
+            .request(Op::Event)
+            .path(path)?
+            .selector(selector)?
+            .value(value)?
+            .submit()
+            .await?;
+
+        match reply.op() {
+            Op::Response => Ok(reply),
+            _ => Err(Error::InvalidData),
         }
     }
 
@@ -155,14 +343,58 @@ impl Client {
         selector: impl Into<Selector<'b>>,
         argument: impl ToDicey,
     ) -> Result<Message, Error> {
+        self.exec_with(path, selector, |builder| builder.set(argument))
+            .await
+    }
+
+    // execs an operation and extracts its result as T, turning error replies into errors. The view itself can't be
+    // returned, as it borrows from the reply
+    pub async fn call<'b, T>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+        args: impl ToDicey,
+    ) -> Result<T, Error>
+    where
+        T: for<'v> FromDicey<'v>,
+    {
+        self.exec(path, selector, args).await?.into_typed()
+    }
+
+    pub async fn exec_with<'b, F>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+        op: F,
+    ) -> Result<Message, Error>
+    where
+        F: FnOnce(&mut ValueBuilder) -> Result<(), Error>,
+    {
         self.request(Op::Exec)
             .path(path)?
             .selector(selector)?
-            .value(argument)?
+            .value_with(op)?
             .submit()
             .await
     }
 
+    /// Like [`Client::set_raw`], but for operations: returns the server's reply as-is, even when it carries an error
+    /// value.
+    pub async fn exec_raw<'b>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+        argument: impl ToDicey,
+    ) -> Result<Message, Error> {
+        self.request(Op::Exec)
+            .path(path)?
+            .selector(selector)?
+            .value(argument)?
+            .submit_raw()
+            .await
+    }
+
+    /// Reads a single property; see [`blocking::Client::get`](crate::blocking::Client::get) for reading whole objects.
     pub async fn get<'b>(
         &self,
         path: impl Into<Vec<u8>>,
@@ -175,45 +407,108 @@ impl Client {
             .await
     }
 
+    /// Returns the protocol version this client advertised when it started the handshake.
+    ///
+    /// This is not the server's version: libdicey doesn't expose the `Hello` the server replies with. A successful
+    /// connection only means the server accepted this version. Returns `None` before the handshake started.
+    pub fn handshake_version(&self) -> Option<Version> {
+        self.state.handshake_version.get().copied()
+    }
+
     pub async fn inspect(&self, path: impl Into<Vec<u8>>) -> Result<ObjectInfo, Error> {
-        let path = path.into();
-        let path_str = String::from_utf8(path.clone()).map_err(|_| Error::InvalidData)?;
+        self.inspect_timeout(path, DEFAULT_TIMEOUT_MS).await
+    }
 
-        self.get(
+    pub async fn inspect_timeout(
+        &self,
+        path: impl Into<Vec<u8>>,
+        timeout_ms: u32,
+    ) -> Result<ObjectInfo, Error> {
+        self.inspect_with(
             path,
             (
                 DICEY_INTROSPECTION_TRAIT_NAME.as_ref(),
                 DICEY_INTROSPECTION_DATA_PROP_NAME.as_ref(),
             ),
+            timeout_ms,
         )
         .await
-        .and_then(move |m| match m.value() {
-            Some(ValueView::Error(e)) => Err(Error::from(e.code as dicey_error)),
-            Some(ref view) => ObjectInfo::from_dicey(path_str, view),
-            _ => Err(Error::BadMessage),
-        })
+    }
+
+    pub async fn inspect_via<'b>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+    ) -> Result<ObjectInfo, Error> {
+        self.inspect_with(path, selector, DEFAULT_TIMEOUT_MS).await
     }
 
     pub async fn inspect_as_xml(&self, path: impl Into<Vec<u8>>) -> Result<String, Error> {
-        self.get(
-            path,
-            (
+        self.inspect_as_xml_timeout(path, DEFAULT_TIMEOUT_MS).await
+    }
+
+    pub async fn inspect_as_xml_timeout(
+        &self,
+        path: impl Into<Vec<u8>>,
+        timeout_ms: u32,
+    ) -> Result<String, Error> {
+        self.request(Op::Get)
+            .path(path)?
+            .selector((
                 DICEY_INTROSPECTION_TRAIT_NAME.as_ref(),
                 DICEY_INTROSPECTION_XML_PROP_NAME.as_ref(),
-            ),
-        )
-        .await
-        .and_then(|m| match m.value() {
-            Some(ValueView::String(s)) => Ok(s.to_owned()),
-            Some(ValueView::Error(e)) => Err(Error::from(e.code as dicey_error)),
-            _ => Err(Error::BadMessage),
-        })
+            ))?
+            .timeout(timeout_ms)
+            .submit()
+            .await?
+            .into_typed()
+    }
+
+    pub async fn is_property_writable<'b>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+    ) -> Result<bool, Error> {
+        let sel = selector.into();
+
+        self.inspect(path).await?.is_property_writable(sel)
     }
 
     pub fn is_running(&self) -> bool {
         !self.ptr().is_null() && unsafe { dicey_client_is_running(self.ptr()) }
     }
 
+    pub fn property<'b, T>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+    ) -> PropertyHandle<'_, T>
+    where
+        T: for<'v> FromDicey<'v> + ToDicey,
+    {
+        PropertyHandle {
+            client: self,
+            path: path.into(),
+            selector: selector.into().to_owned(),
+            _type: PhantomData,
+        }
+    }
+
+    /// Resolves once the client can take requests.
+    ///
+    /// `connect` only returns after the handshake has completed, so this resolves right away on a live client. The
+    /// client doesn't reconnect on its own: once the connection is lost this fails with [`Error::ConnectionReset`]
+    /// instead of waiting, or with [`Error::Cancelled`] if the client was closed.
+    pub async fn ready(&self) -> Result<(), Error> {
+        if self.state.closing.load(Ordering::Acquire) {
+            Err(Error::Cancelled)
+        } else if self.is_running() {
+            Ok(())
+        } else {
+            Err(Error::ConnectionReset)
+        }
+    }
+
     pub fn request(&self, op: Op) -> RequestBuilder {
         RequestBuilder::new(self, op)
     }
@@ -230,23 +525,30 @@ impl Client {
         ) {
             debug_assert!(!client.is_null() && !ctx.is_null() && !packet.is_null());
 
-            let tx = unsafe { ManuallyDrop::into_inner(ptr::read(ctx as *mut ManuallyDrop<Tx>)) };
+            let tx = unsafe { Box::from_raw(ctx as *mut Tx) };
+            let state = unsafe { ClientState::from_client(client) };
 
-            let status = Error::from(status);
+            // the receiver is gone if the caller dropped the request future, in which case the reply is just discarded
+            let _ = tx.send(unsafe { state.take_reply(status, packet) });
+        }
 
-            tx.send(if status == Error::OK {
-                let packet = unsafe { ptr::replace(packet, mem::zeroed()) };
+        if self.state.closing.load(Ordering::Acquire) {
+            return Err(Error::Cancelled);
+        }
 
-                Message::from_raw(packet)
-            } else {
-                Err(status)
-            })
-            .expect("receiver should never drop before here")
+        self.state.stats.on_request();
+
+        if let Some(inspect) = &self.state.inspect {
+            inspect.lock().expect("inspector poisoned")(Direction::Outbound, msg.raw_bytes());
         }
 
         let (tx, rx) = oneshot::channel::<RespResult>();
 
-        let mut tx = ManuallyDrop::new(tx);
+        // must be created before submitting, so that a concurrent cancel_all isn't missed
+        let cancelled = pin!(self.state.cancel.notified());
+
+        // the sender is moved into the heap, given that the callback may run after this future has been dropped
+        let tx = Box::into_raw(Box::new(tx));
 
         unsafe {
             if let Err(err) = ccall!(
@@ -254,16 +556,35 @@ impl Client {
                 self.ptr(),
                 msg.into_raw(),
                 Some(reply_cb),
-                &mut tx as *mut _ as *mut c_void,
+                tx as *mut c_void,
                 timeout_ms
             ) {
-                ManuallyDrop::drop(&mut tx);
+                drop(Box::from_raw(tx));
+
+                self.state.stats.on_reply(&Err::<(), _>(err));
 
                 return Err(err);
             }
         }
 
-        rx.await.expect("sender should never drop before here")
+        match select(rx, cancelled).await {
+            Either::Left((reply, _)) => reply.expect("sender should never drop before here"),
+            Either::Right(_) => Err(Error::Cancelled),
+        }
+    }
+
+    // pending requests are not affected
+    pub fn reset_stats(&self) {
+        self.state.stats.reset();
+    }
+
+    // sends an already serialized request, such as one captured for replay or forwarded by a proxy. Only messages are
+    // accepted; hellos and byes are part of the handshake the client already takes care of
+    pub async fn send_raw(&self, bytes: &[u8], timeout_ms: u32) -> Result<Message, Error> {
+        match Packet::load(bytes)? {
+            Packet::Message(msg) => self.request_with(msg, timeout_ms).await,
+            _ => Err(Error::InvalidData),
+        }
     }
 
     pub async fn set<'b>(
@@ -272,50 +593,158 @@ impl Client {
         selector: impl Into<Selector<'b>>,
         argument: impl ToDicey,
     ) -> Result<(), Error> {
+        self.set_with(path, selector, |builder| builder.set(argument))
+            .await
+    }
+
+    pub async fn set_with<'b, F>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+        op: F,
+    ) -> Result<(), Error>
+    where
+        F: FnOnce(&mut ValueBuilder) -> Result<(), Error>,
+    {
         let msg = self
             .request(Op::Set)
             .path(path)?
             .selector(selector)?
-            .value(argument)?
+            .value_with(op)?
             .submit()
             .await?;
 
-        debug_assert!(
-            msg.value()
-                .and_then(|ref v| <()>::from_dicey(v).ok())
-                .is_some()
-        );
+        let value = msg.reply_value()?;
+        debug_assert!(<()>::from_dicey(&value).is_ok());
 
         Ok(())
     }
 
+    /// Like [`Client::set`], but returns the server's reply as-is, even when it carries an error value.
+    pub async fn set_raw<'b>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+        argument: impl ToDicey,
+    ) -> Result<Message, Error> {
+        self.request(Op::Set)
+            .path(path)?
+            .selector(selector)?
+            .value(argument)?
+            .submit()
+            .await
+    }
+
+    pub async fn set_strict<'b>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+        argument: impl ToDicey,
+    ) -> Result<(), Error> {
+        let path = path.into();
+        let sel = selector.into();
+
+        if !self.is_property_writable(path.clone(), sel).await? {
+            return Err(Error::InvalidData);
+        }
+
+        self.set(path, sel, argument).await
+    }
+
+    pub fn stats(&self) -> ClientStats {
+        self.state.stats.snapshot()
+    }
+
+    // dicey.SignalManager only has Subscribe and Unsubscribe. Servers don't expose the subscriptions they recorded for a
+    // client, so there's no server-side list to reconcile against
     pub async fn subscribe_to<'b>(
         &self,
         path: impl AsRef<str>,
         selector: impl Into<Selector<'b>>,
     ) -> Result<(), Error> {
-        // strip the null terminator, otherwise path() and friends will choke due to `CString::new` actively checking
-        // null characters
-        let server_path = &DICEY_SERVER_PATH[..DICEY_SERVER_PATH.len() - 1];
-        let eventmanager_trait_name =
-            &DICEY_EVENTMANAGER_TRAIT_NAME[..DICEY_EVENTMANAGER_TRAIT_NAME.len() - 1];
-        let eventmanager_subscribe_op_name =
-            &DICEY_EVENTMANAGER_SUBSCRIBE_OP_NAME[..DICEY_EVENTMANAGER_SUBSCRIBE_OP_NAME.len() - 1];
+        self.subscribe_aliased(path, selector).await.map(|_| ())
+    }
 
-        self.request(Op::Exec)
-            .path(server_path)?
-            .selector(Selector {
-                trait_name: eventmanager_trait_name,
-                elem: eventmanager_subscribe_op_name,
-            })?
-            .value((Path::new(path.as_ref()), selector.into()))?
-            .submit()
-            .await
-            .and_then(|m| match m.value() {
-                Some(ValueView::Unit) => Ok(()),
-                Some(ValueView::Error(e)) => Err(Error::from(e.code as dicey_error)),
-                _ => Err(Error::BadMessage),
-            })
+    // the requests are sent one at a time, as libdicey drops packets pipelined on the same connection. Every
+    // subscription is attempted even after one fails; on failure the error carries a PartialSubscription telling which
+    // ones went through and must be unsubscribed to roll back
+    pub async fn subscribe_many<'b, P: AsRef<str>>(
+        &self,
+        subs: &[(P, Selector<'b>)],
+    ) -> Result<Vec<Option<PathBuf>>, DiceyError> {
+        let mut results = Vec::with_capacity(subs.len());
+
+        for (path, sel) in subs {
+            results.push(self.subscribe_aliased(path, *sel).await);
+        }
+
+        collect_subscriptions(results)
+    }
+
+    pub async fn subscribe_stream<'b>(
+        &self,
+        path: impl AsRef<str>,
+        selector: impl Into<Selector<'b>>,
+    ) -> Result<Subscription<'_>, Error> {
+        let path = path.as_ref();
+        let sel = selector.into();
+
+        let cpath = bytes_to_cpath(path)?;
+        let tname = bytes_to_cstring(sel.trait_name)?;
+        let elem = bytes_to_cstring(sel.elem)?;
+
+        // subscribe to the broadcast channel before the server, otherwise we may lose the first events
+        let signals = self.state.subscribe();
+
+        self.subscribe_to(path, sel).await?;
+
+        let filter = (
+            cpath.to_bytes().to_owned(),
+            tname.to_bytes().to_owned(),
+            elem.to_bytes().to_owned(),
+        );
+
+        let events = stream::unfold((signals, filter), |(mut signals, filter)| async move {
+            use RecvError::*;
+
+            loop {
+                match signals.recv().await {
+                    Ok(msg) => {
+                        let (path, trait_name, elem) = &filter;
+
+                        if msg.path_bytes() == &path[..]
+                            && msg.selector() == (&trait_name[..], &elem[..]).into()
+                        {
+                            return Some((msg, (signals, filter)));
+                        }
+                    }
+                    Err(Lagged(_)) => continue,
+                    Err(Closed) => return None,
+                }
+            }
+        })
+        .boxed();
+
+        Ok(Subscription {
+            client: self,
+            path: cpath,
+            tname,
+            elem,
+            events,
+        })
+    }
+
+    /// Lists the names of the traits implemented by the object at `path`, without decoding their elements.
+    pub async fn traits(&self, path: impl Into<Vec<u8>>) -> Result<Vec<String>, Error> {
+        self.get(
+            path,
+            (
+                DICEY_INTROSPECTION_TRAIT_NAME.as_ref(),
+                DICEY_INTROSPECTION_DATA_PROP_NAME.as_ref(),
+            ),
+        )
+        .await
+        .and_then(|m| parse_trait_names(&m))
     }
 
     pub async fn unsubscribe_from<'b>(
@@ -331,20 +760,59 @@ impl Client {
             })?
             .value((Path::new(path.as_ref()), selector.into()))?
             .submit()
-            .await
-            .and_then(|m| match m.value() {
-                Some(ValueView::Unit) => Ok(()),
-                Some(ValueView::Error(e)) => Err(Error::from(e.code as dicey_error)),
-                _ => Err(Error::BadMessage),
-            })
+            .await?
+            .into_typed()
     }
 
-    fn ptr(&self) -> *mut dicey_client {
-        self.state.ptr
+    // the server replies with the path the subscription was recorded under when `path` is an alias
+    async fn subscribe_aliased<'b>(
+        &self,
+        path: impl AsRef<str>,
+        selector: impl Into<Selector<'b>>,
+    ) -> Result<Option<PathBuf>, Error> {
+        // the builder takes care of the null terminators of these constants
+        let reply = self
+            .request(Op::Exec)
+            .path(DICEY_SERVER_PATH)?
+            .selector(Selector {
+                trait_name: DICEY_EVENTMANAGER_TRAIT_NAME,
+                elem: DICEY_EVENTMANAGER_SUBSCRIBE_OP_NAME,
+            })?
+            .value((Path::new(path.as_ref()), selector.into()))?
+            .submit()
+            .await?;
+
+        match reply.reply_value()? {
+            ValueView::Unit => Ok(None),
+            ValueView::Path(path) => Ok(Some(path.to_owned())),
+            _ => Err(Error::InvalidData),
+        }
     }
-}
 
-impl Drop for Client {
+    async fn inspect_with<'b>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+        timeout_ms: u32,
+    ) -> Result<ObjectInfo, Error> {
+        let path = path.into();
+        let path_str = String::from_utf8(path.clone()).map_err(|_| Error::InvalidData)?;
+
+        self.request(Op::Get)
+            .path(path)?
+            .selector(selector)?
+            .timeout(timeout_ms)
+            .submit()
+            .await
+            .and_then(move |m| ObjectInfo::from_dicey(path_str, &m.reply_value()?))
+    }
+
+    fn ptr(&self) -> *mut dicey_client {
+        self.state.ptr
+    }
+}
+
+impl Drop for Client {
     fn drop(&mut self) {
         unsafe {
             //attempt disconnecting. We don't really care about the result.
@@ -355,39 +823,181 @@ impl Drop for Client {
     }
 }
 
+// libdicey can't delete a client that's still connecting, so a connect future dropped midway blocks until the
+// handshake is over, one way or the other, before the client goes
+struct PendingConnect {
+    client: Option<Client>,
+    rx: oneshot::Receiver<Result<(), Error>>,
+}
+
+impl Drop for PendingConnect {
+    fn drop(&mut self) {
+        if self.client.is_some() {
+            let _ = futures::executor::block_on(&mut self.rx);
+        }
+    }
+}
+
 pub struct SignalSource {
-    signals: Receiver<Arc<Message>>,
+    signals: EventReceiver,
 }
 
 impl SignalSource {
+    // the number of events this source has yet to read
+    pub fn len(&self) -> usize {
+        self.signals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signals.is_empty()
+    }
+
     pub async fn next(&mut self) -> Result<Arc<Message>, Error> {
         self.signals.recv().await.map_err(|e| {
-            use tokio::sync::broadcast::error::RecvError::*;
+            use RecvError::*;
 
             match e {
                 Closed => Error::Cancelled,
-                Lagged(_) => Error::TimedOut,
+                Lagged(_) => Error::Overflow,
             }
         })
     }
 
+    /// Returns a new source for the same events, to be handed to another consumer.
+    ///
+    /// The new source starts out empty and only gets the events received from now on, not the ones this source has yet
+    /// to read, as with [`tokio::sync::broadcast::Receiver::resubscribe`].
+    pub fn resubscribe(&self) -> SignalSource {
+        SignalSource {
+            signals: self.signals.resubscribe(),
+        }
+    }
+
     pub fn poll(&mut self) -> Result<Arc<Message>, Error> {
-        use tokio::sync::broadcast::error::TryRecvError::*;
+        use TryRecvError::*;
 
         match self.signals.try_recv() {
             Ok(msg) => Ok(msg),
             Err(Empty) => Err(Error::TryAgain),
             Err(Closed) => Err(Error::Cancelled),
-            Err(Lagged(_)) => Err(Error::TimedOut),
+            Err(Lagged(_)) => Err(Error::Overflow),
+        }
+    }
+
+    // discards every event queued so far without waiting for new ones, returning how many were dropped. Events this
+    // source already lagged behind on count as dropped too
+    pub fn drain(&mut self) -> usize {
+        self.drain_keeping_last().0
+    }
+
+    // like drain, but hands back the most recent of the discarded events, if any
+    pub fn skip_to_latest(&mut self) -> Option<Arc<Message>> {
+        self.drain_keeping_last().1
+    }
+
+    fn drain_keeping_last(&mut self) -> (usize, Option<Arc<Message>>) {
+        use TryRecvError::*;
+
+        let mut dropped = 0usize;
+        let mut last = None;
+
+        loop {
+            match self.signals.try_recv() {
+                Ok(msg) => {
+                    dropped += 1;
+                    last = Some(msg);
+                }
+                Err(Lagged(n)) => dropped = dropped.saturating_add(n as usize),
+                Err(Empty | Closed) => break (dropped, last),
+            }
         }
     }
 }
 
+pub struct Subscription<'a> {
+    client: &'a Client,
+
+    path: CString,
+    tname: CString,
+    elem: CString,
+
+    events: BoxStream<'static, Arc<Message>>,
+}
+
+impl Stream for Subscription<'_> {
+    type Item = Arc<Message>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.events.poll_next_unpin(cx)
+    }
+}
+
+impl Drop for Subscription<'_> {
+    fn drop(&mut self) {
+        // we can't await in drop, so we fire the unsubscribe request and forget about it
+        extern "C" fn unsub_cb(_: *mut dicey_client, _: *mut c_void, _: dicey_error) {}
+
+        let csel = dicey_selector {
+            trait_: self.tname.as_ptr(),
+            elem: self.elem.as_ptr(),
+        };
+
+        unsafe {
+            dicey_client_unsubscribe_from_async(
+                self.client.ptr(),
+                self.path.as_ptr(),
+                csel,
+                Some(unsub_cb),
+                ptr::null_mut(),
+                DEFAULT_TIMEOUT_MS,
+            );
+        }
+    }
+}
+
+/// A property of a remote object, bound to its path and selector and read and written as a `T`.
+pub struct PropertyHandle<'a, T> {
+    client: &'a Client,
+
+    path: Vec<u8>,
+    selector: SelectorBuf,
+
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T> PropertyHandle<'_, T>
+where
+    T: for<'v> FromDicey<'v> + ToDicey,
+{
+    pub async fn get(&self) -> Result<T, Error> {
+        self.client
+            .get(self.path.clone(), &self.selector)
+            .await?
+            .into_typed()
+    }
+
+    pub fn path(&self) -> &[u8] {
+        &self.path
+    }
+
+    pub fn selector(&self) -> Selector<'_> {
+        self.selector.as_selector()
+    }
+
+    pub async fn set(&self, value: T) -> Result<(), Error> {
+        self.client
+            .set(self.path.clone(), &self.selector, value)
+            .await
+    }
+}
+
 pub struct RequestBuilder<'a> {
     client: &'a Client,
 
     mbuilder: MessageBuilder,
     timeout_ms: u32,
+    idempotency_key: Option<Uuid>,
+    has_value: bool,
 }
 
 impl<'a> RequestBuilder<'a> {
@@ -397,9 +1007,45 @@ impl<'a> RequestBuilder<'a> {
             mbuilder: MessageBuilder::new(op)
                 .expect("failed to create message builder (out of memory?)"),
             timeout_ms: DEFAULT_TIMEOUT_MS,
+            idempotency_key: None,
+            has_value: false,
         }
     }
 
+    pub fn current_selector(&self) -> Option<Selector<'_>> {
+        self.mbuilder.current_selector()
+    }
+
+    pub const fn current_seq(&self) -> Option<u32> {
+        self.mbuilder.current_seq()
+    }
+
+    /// Tags the request with a key servers can use to recognise repeated attempts of the same operation.
+    ///
+    /// The key is sent together with the value as a `{#v}` pair, so it must be set before the value and only makes sense
+    /// with servers that expect it. Setting it once the value is set fails with [`Error::InvalidData`].
+    pub fn idempotency_key(self, key: Uuid) -> Result<Self, Error> {
+        if self.has_value {
+            return Err(Error::InvalidData);
+        }
+
+        Ok(Self {
+            idempotency_key: Some(key),
+            ..self
+        })
+    }
+
+    pub const fn op(&self) -> Op {
+        self.mbuilder.op()
+    }
+
+    /// Makes the request wait for a reply indefinitely.
+    ///
+    /// Dropping the future returned by `submit` abandons the request; a late reply is then discarded.
+    pub fn no_timeout(self) -> Self {
+        self.timeout(NO_TIMEOUT)
+    }
+
     pub fn path(self, path: impl Into<Vec<u8>>) -> Result<Self, Error> {
         Ok(Self {
             mbuilder: self.mbuilder.path(path)?,
@@ -407,6 +1053,10 @@ impl<'a> RequestBuilder<'a> {
         })
     }
 
+    pub fn path_str(&self) -> Option<&str> {
+        self.mbuilder.path_str()
+    }
+
     pub fn selector<'b>(self, sel: impl Into<Selector<'b>>) -> Result<Self, Error> {
         Ok(Self {
             mbuilder: self.mbuilder.selector(sel)?,
@@ -414,6 +1064,15 @@ impl<'a> RequestBuilder<'a> {
         })
     }
 
+    pub fn selector_cstr(self, trait_name: &CStr, elem: &CStr) -> Result<Self, Error> {
+        Ok(Self {
+            mbuilder: self.mbuilder.selector_cstr(trait_name, elem)?,
+            ..self
+        })
+    }
+
+    /// Sets the sequence number of the request, which is overridden when it's sent, as explained by
+    /// [`blocking::RequestBuilder::seq`](crate::blocking::RequestBuilder::seq).
     pub fn seq(self, seq: u32) -> Self {
         Self {
             mbuilder: self.mbuilder.seq(seq),
@@ -421,40 +1080,313 @@ impl<'a> RequestBuilder<'a> {
         }
     }
 
+    // only timeouts are retried, given that they're the only errors a new attempt may fix. Exec requests are only retried
+    // when they carry an idempotency key, because a timed out Exec may still have been executed by the server
+    pub async fn submit_with_retries(self, attempts: u32) -> Result<Message, Error> {
+        if self.mbuilder.op() == Op::Exec && self.idempotency_key.is_none() && attempts > 1 {
+            return Err(Error::InvalidData);
+        }
+
+        let msg = self.mbuilder.build()?;
+
+        for _ in 1..attempts {
+            match self.client.request_with(msg.clone(), self.timeout_ms).await {
+                Err(Error::TimedOut) => continue,
+                res => return res,
+            }
+        }
+
+        self.client.request_with(msg, self.timeout_ms).await
+    }
+
+    // the reply is returned as-is, error values included
     pub async fn submit(self) -> Result<Message, Error> {
         self.client
             .request_with(self.mbuilder.build()?, self.timeout_ms)
             .await
     }
 
+    /// Sends the request and returns the reply verbatim, even when it carries an error value.
+    pub async fn submit_raw(self) -> Result<Message, Error> {
+        self.submit().await
+    }
+
+    /// Sends the request, then keeps yielding the events that follow it, such as progress updates.
+    ///
+    /// dicey can't tie an event to the request that caused it, so the stream goes by convention: after the reply, it
+    /// yields every event the request's object emits on the request's trait, in the order they arrive. The stream ends
+    /// after an event carrying unit, which marks completion and is yielded too, or after the first error: an error
+    /// reply, an event carrying an error value, or `Error::Overflow` if events were lost because the stream fell behind.
+    ///
+    /// Servers only send events to their subscribers, so the client must already be subscribed to the signals the
+    /// operation emits.
+    pub fn submit_stream(self) -> impl Stream<Item = Result<Message, Error>> + 'a {
+        let client = self.client;
+        let timeout_ms = self.timeout_ms;
+
+        // listen before sending, or the first events may be gone before the reply is even in
+        let request = self
+            .mbuilder
+            .build()
+            .map(|msg| (msg, client.state.subscribe()));
+
+        stream::once(async move {
+            let (msg, signals) = request?;
+
+            let path = msg.path().into_owned();
+            let trait_name = msg.selector().trait_name.to_owned();
+
+            let reply = client.request_with(msg, timeout_ms).await?;
+
+            reply.reply_value()?;
+
+            Ok((reply, correlated_events(signals, path, trait_name)))
+        })
+        .flat_map(|res| match res {
+            Ok((reply, events)) => stream::once(future::ready(Ok(reply)))
+                .chain(events)
+                .left_stream(),
+            Err(err) => stream::once(future::ready(Err(err))).right_stream(),
+        })
+    }
+
     pub fn timeout(self, timeout_ms: u32) -> Self {
         Self { timeout_ms, ..self }
     }
 
     pub fn value(self, value: impl ToDicey) -> Result<Self, Error> {
-        Ok(Self {
-            mbuilder: self.mbuilder.value(value)?,
-            ..self
-        })
+        self.value_with(|builder| builder.set(value))
     }
 
     pub fn value_with<F>(self, op: F) -> Result<Self, Error>
     where
         F: FnOnce(&mut ValueBuilder) -> Result<(), Error>,
     {
+        let mbuilder = self.mbuilder.value_with_key(self.idempotency_key, op)?;
+
         Ok(Self {
-            mbuilder: self.mbuilder.value_with(op)?,
+            mbuilder,
+            has_value: true,
             ..self
         })
     }
 }
 
+// the events following a streamed request, as described by RequestBuilder::submit_stream
+fn correlated_events(
+    signals: EventReceiver,
+    path: String,
+    trait_name: Vec<u8>,
+) -> impl Stream<Item = Result<Message, Error>> {
+    stream::unfold(Some(signals), move |signals| {
+        let (path, trait_name) = (path.clone(), trait_name.clone());
+
+        async move {
+            use RecvError::*;
+
+            let mut signals = signals?;
+
+            loop {
+                match signals.recv().await {
+                    Ok(event)
+                        if event.path_bytes() == path.as_bytes()
+                            && event.selector().trait_name == trait_name =>
+                    {
+                        let event = Arc::unwrap_or_clone(event);
+
+                        return match event.value() {
+                            Some(ValueView::Error(e)) => Some((Err(e.into_error()), None)),
+                            Some(ValueView::Unit) => Some((Ok(event), None)),
+                            _ => Some((Ok(event), Some(signals))),
+                        };
+                    }
+                    Ok(_) => continue,
+                    Err(Lagged(_)) => return Some((Err(Error::Overflow), None)),
+                    Err(Closed) => return None,
+                }
+            }
+        }
+    })
+}
+
+// the receiving end of either kind of event queue, reporting errors as broadcast receivers do. Unbounded queues never
+// lag, and only close with their client
+enum EventReceiver {
+    Broadcast(Receiver<Arc<Message>>),
+
+    // the queues are only kept by the client, and looked up again to resubscribe
+    Unbounded(UnboundedReceiver<Arc<Message>>, Weak<UnboundedQueues>),
+}
+
+type UnboundedQueues = Mutex<Vec<UnboundedSender<Arc<Message>>>>;
+
+struct SizedQueues {
+    queues: Vec<Sender<Arc<Message>>>,
+    added: UnboundedReceiver<Sender<Arc<Message>>>,
+}
+
+// once the client is gone, the receiver is closed from the start
+fn unbounded_receiver(queues: &Weak<UnboundedQueues>) -> EventReceiver {
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    if let Some(queues) = queues.upgrade() {
+        queues.lock().expect("event queues poisoned").push(tx);
+    }
+
+    EventReceiver::Unbounded(rx, queues.clone())
+}
+
+impl EventReceiver {
+    fn is_empty(&self) -> bool {
+        match self {
+            EventReceiver::Broadcast(rx) => rx.is_empty(),
+            EventReceiver::Unbounded(rx, _) => rx.is_empty(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            EventReceiver::Broadcast(rx) => rx.len(),
+            EventReceiver::Unbounded(rx, _) => rx.len(),
+        }
+    }
+
+    fn resubscribe(&self) -> Self {
+        match self {
+            EventReceiver::Broadcast(rx) => EventReceiver::Broadcast(rx.resubscribe()),
+            EventReceiver::Unbounded(_, queues) => unbounded_receiver(queues),
+        }
+    }
+
+    async fn recv(&mut self) -> Result<Arc<Message>, RecvError> {
+        match self {
+            EventReceiver::Broadcast(rx) => rx.recv().await,
+            EventReceiver::Unbounded(rx, _) => rx.recv().await.ok_or(RecvError::Closed),
+        }
+    }
+
+    fn try_recv(&mut self) -> Result<Arc<Message>, TryRecvError> {
+        match self {
+            EventReceiver::Broadcast(rx) => rx.try_recv(),
+            EventReceiver::Unbounded(rx, _) => rx.try_recv().map_err(|err| match err {
+                mpsc::error::TryRecvError::Empty => TryRecvError::Empty,
+                mpsc::error::TryRecvError::Disconnected => TryRecvError::Closed,
+            }),
+        }
+    }
+}
+
+impl From<Receiver<Arc<Message>>> for EventReceiver {
+    fn from(rx: Receiver<Arc<Message>>) -> Self {
+        EventReceiver::Broadcast(rx)
+    }
+}
+
+/// Bounds a future by a wall-clock timeout, reporting its expiry as [`Error::TimedOut`].
+///
+/// The timeout is applied on top of any set on the request itself, and must be awaited inside a Tokio runtime with the
+/// time driver enabled.
+pub async fn timeout<F, T>(duration: Duration, fut: F) -> Result<T, Error>
+where
+    F: Future<Output = Result<T, Error>>,
+{
+    tokio::time::timeout(duration, fut)
+        .await
+        .unwrap_or(Err(Error::TimedOut))
+}
+
 // we must put the client internal state in a separate struct we then allocate into the heap,
 // otherwise we can't really pin it to a specific memory location
 struct ClientState {
     ptr: *mut dicey_client,
 
     events: Sender<Arc<Message>>,
+    event_queue_size: usize,
+    event_sink: EventSink,
+
+    // queues made by events_with_capacity are handed to the event loop through a channel rather than a shared list, so
+    // that the loop never waits on a thread creating a source. Only the loop locks sized_events
+    new_sized_events: UnboundedSender<Sender<Arc<Message>>>,
+    sized_events: Mutex<SizedQueues>,
+    unbounded_events: Arc<UnboundedQueues>,
+    handshake_version: OnceLock<Version>,
+    stats: StatsCounters,
+
+    // outbound packets are seen from the requesting task, inbound ones from the event loop
+    inspect: Option<Mutex<Inspector>>,
+
+    closing: AtomicBool,
+    cancel: Notify,
+}
+
+// libdicey clients can be handed requests from any thread, given they are queued into the client's own loop, and the
+// rest of the state is already shared with the callbacks running on that loop
+unsafe impl Send for ClientState {}
+unsafe impl Sync for ClientState {}
+
+impl ClientState {
+    unsafe fn from_client<'a>(client: *mut dicey_client) -> &'a Self {
+        unsafe { &*(dicey_client_get_context(client) as *const ClientState) }
+    }
+
+    // a new receiver for every event from now on, of the kind of queue the client was created with
+    fn subscribe(&self) -> EventReceiver {
+        match self.event_sink {
+            EventSink::Bounded => self.events.subscribe().into(),
+            EventSink::Unbounded => unbounded_receiver(&Arc::downgrade(&self.unbounded_events)),
+        }
+    }
+
+    unsafe fn take_reply(
+        &self,
+        status: dicey_error,
+        packet: *mut dicey_packet,
+    ) -> Result<Message, Error> {
+        let status = Error::from(status);
+
+        let reply = if status == Error::OK {
+            let packet = unsafe { ptr::replace(packet, mem::zeroed()) };
+
+            Message::from_raw(packet)
+        } else {
+            Err(status)
+        };
+
+        self.stats.on_reply(&reply);
+
+        reply
+    }
+}
+
+unsafe extern "C" fn client_on_inspect(
+    _: *mut dicey_client,
+    ctx: *mut c_void,
+    event: dicey_client_event,
+) {
+    // events raised by dicey_client_new come before the context is set
+    if ctx.is_null() {
+        return;
+    }
+
+    let state = unsafe { &*(ctx as *const ClientState) };
+
+    if event.type_ == dicey_client_event_type_DICEY_CLIENT_EVENT_HANDSHAKE_START {
+        let _ = state
+            .handshake_version
+            .set(unsafe { event.__bindgen_anon_1.version }.into());
+    } else if event.type_ == dicey_client_event_type_DICEY_CLIENT_EVENT_MESSAGE_RECEIVING
+        && let Some(inspect) = &state.inspect
+        && let Ok(mut inspect) = inspect.lock()
+    {
+        // libdicey never raises MESSAGE_SENDING, which is why outbound packets are reported by request_with instead
+        let packet = unsafe { event.__bindgen_anon_1.packet };
+
+        if !packet.payload.is_null() {
+            inspect(Direction::Inbound, unsafe {
+                slice::from_raw_parts(packet.payload as *const u8, packet.nbytes)
+            });
+        }
+    }
 }
 
 unsafe extern "C" fn client_on_event(
@@ -465,12 +1397,613 @@ unsafe extern "C" fn client_on_event(
     let state = unsafe {
         assert!(!c_client.is_null() && !ctx.is_null() && dicey_packet_is_valid(*packet));
 
-        &mut *(dicey_client_get_context(c_client) as *mut ClientState)
+        // requests may be holding shared references to the state on other threads, so only take a shared one
+        &*(dicey_client_get_context(c_client) as *const ClientState)
     };
 
-    // if there are no subscribers, we can just drop the message
-    let _ = state.events.send(Arc::new(
+    state.stats.on_event();
+
+    let msg = Arc::new(
         Message::from_raw(unsafe { ptr::replace(packet, mem::zeroed()) })
             .expect("failed to convert packet to message"),
-    ));
+    );
+
+    // queues sized by events_with_capacity only live as long as their sources
+    if let Ok(mut sized_events) = state.sized_events.try_lock() {
+        let SizedQueues { queues, added } = &mut *sized_events;
+
+        while let Ok(events) = added.try_recv() {
+            queues.push(events);
+        }
+
+        queues.retain(|events| events.send(msg.clone()).is_ok());
+    }
+
+    // and so do unbounded ones
+    if let Ok(mut unbounded_events) = state.unbounded_events.lock() {
+        unbounded_events.retain(|events| events.send(msg.clone()).is_ok());
+    }
+
+    // if there are no subscribers, we can just drop the message
+    let _ = state.events.send(msg);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{ErrorMessage, OwnedValue};
+
+    fn signal(path: &str, selector: (&str, &str), value: impl ToDicey, seq: u32) -> Arc<Message> {
+        let msg = MessageBuilder::event()
+            .unwrap()
+            .path(path)
+            .unwrap()
+            .selector(selector)
+            .unwrap()
+            .value(value)
+            .unwrap()
+            .seq(seq)
+            .build()
+            .unwrap();
+
+        Arc::new(msg)
+    }
+
+    fn event(seq: u32) -> Arc<Message> {
+        signal("/foo", ("a.Trait", "Signal"), seq, seq)
+    }
+
+    #[test]
+    fn test_connect_future_is_send() {
+        fn assert_send<T: Send>(_: &T) {}
+
+        assert_send(&Client::connect_future(ClientArgs::new(
+            "/tmp/dicey-test.sock",
+        )));
+    }
+
+    #[test]
+    fn test_drain_events() {
+        let events = Sender::new(4);
+        let mut source = SignalSource {
+            signals: events.subscribe().into(),
+        };
+
+        assert_eq!(source.drain(), 0);
+        assert!(source.skip_to_latest().is_none());
+
+        // overflow the queue, so that some of the events are lost before draining
+        for seq in 0..6 {
+            events.send(event(seq)).unwrap();
+        }
+
+        assert_eq!(source.drain(), 6);
+        assert!(source.is_empty());
+
+        for seq in 6..9 {
+            events.send(event(seq)).unwrap();
+        }
+
+        let latest = source.skip_to_latest().unwrap();
+        assert_eq!(latest.seq(), 8);
+        assert_eq!(source.poll().err(), Some(Error::TryAgain));
+    }
+
+    #[test]
+    fn test_lagged_source() {
+        let events = Sender::new(2);
+        let mut source = SignalSource {
+            signals: events.subscribe().into(),
+        };
+
+        for seq in 0..3 {
+            events.send(event(seq)).unwrap();
+        }
+
+        // the loss is reported once, then the source goes on from the oldest event still queued
+        assert_eq!(source.poll().err(), Some(Error::Overflow));
+        assert_eq!(source.poll().unwrap().seq(), 1);
+        assert_eq!(source.poll().unwrap().seq(), 2);
+    }
+
+    #[test]
+    fn test_unbounded_events() {
+        let queues = Arc::new(Mutex::new(Vec::new()));
+        let mut source = SignalSource {
+            signals: unbounded_receiver(&Arc::downgrade(&queues)),
+        };
+        let tx = queues.lock().unwrap()[0].clone();
+
+        // far more than any bounded queue would hold, and none are lost
+        for seq in 0..(DEFAULT_EVENT_QUEUE_SIZE as u32 * 4) {
+            tx.send(event(seq)).unwrap();
+        }
+
+        assert_eq!(source.len(), DEFAULT_EVENT_QUEUE_SIZE * 4);
+
+        for seq in 0..(DEFAULT_EVENT_QUEUE_SIZE as u32 * 4) {
+            assert_eq!(source.poll().unwrap().seq(), seq);
+        }
+
+        assert_eq!(source.poll().err(), Some(Error::TryAgain));
+
+        // the queues go away with the client
+        drop((tx, queues));
+        assert_eq!(source.poll().err(), Some(Error::Cancelled));
+        assert_eq!(source.resubscribe().poll().err(), Some(Error::Cancelled));
+    }
+
+    #[test]
+    fn test_resubscribe() {
+        let events = Sender::new(8);
+        let queues = Arc::new(Mutex::new(Vec::new()));
+
+        let sources = [
+            SignalSource {
+                signals: events.subscribe().into(),
+            },
+            SignalSource {
+                signals: unbounded_receiver(&Arc::downgrade(&queues)),
+            },
+        ];
+
+        // each source is dropped before the next one is checked, so sends may find no receivers
+        let send = |seq| {
+            let _ = events.send(event(seq));
+            queues
+                .lock()
+                .unwrap()
+                .retain(|tx: &UnboundedSender<_>| tx.send(event(seq)).is_ok());
+        };
+
+        for mut source in sources {
+            send(0);
+
+            // the new source starts from the events sent after it was made
+            let mut other = source.resubscribe();
+            assert!(other.is_empty());
+
+            send(1);
+
+            assert_eq!(source.poll().unwrap().seq(), 0);
+            assert_eq!(source.poll().unwrap().seq(), 1);
+            assert_eq!(other.poll().unwrap().seq(), 1);
+            assert_eq!(other.poll().err(), Some(Error::TryAgain));
+        }
+    }
+
+    #[test]
+    fn test_thread_safety() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        fn assert_send<T: Send>() {}
+
+        assert_send_sync::<Client>();
+        assert_send_sync::<Arc<Message>>();
+        assert_send_sync::<SignalSource>();
+        assert_send::<Subscription<'_>>();
+    }
+
+    #[tokio::test]
+    async fn test_correlated_events() {
+        let event = |path, elem, value: Option<u32>| match value {
+            Some(value) => signal(path, ("a.Job", elem), value, 0),
+            None => signal(path, ("a.Job", elem), (), 0),
+        };
+
+        let events = Sender::new(8);
+        let stream = correlated_events(
+            events.subscribe().into(),
+            "/job".to_string(),
+            b"a.Job".to_vec(),
+        );
+
+        events.send(event("/other", "Progress", Some(1))).unwrap();
+        events.send(event("/job", "Progress", Some(2))).unwrap();
+        events.send(event("/job", "Progress", Some(3))).unwrap();
+        events.send(event("/job", "Done", None)).unwrap();
+        events.send(event("/job", "Progress", Some(4))).unwrap();
+
+        let values = stream
+            .map(|event| event.unwrap().value().unwrap().to_owned_value())
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(
+            values,
+            [
+                OwnedValue::UInt32(2),
+                OwnedValue::UInt32(3),
+                OwnedValue::Unit
+            ]
+        );
+
+        let error = signal(
+            "/job",
+            ("a.Job", "Failed"),
+            ErrorMessage::from(Error::PathNotFound),
+            0,
+        );
+
+        let stream = correlated_events(
+            events.subscribe().into(),
+            "/job".to_string(),
+            b"a.Job".to_vec(),
+        );
+
+        events.send(error).unwrap();
+        events.send(event("/job", "Progress", Some(5))).unwrap();
+
+        assert_eq!(
+            stream.map(|event| event.err()).collect::<Vec<_>>().await,
+            [Some(Error::PathNotFound)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let res = timeout(
+            Duration::from_millis(10),
+            futures::future::pending::<Result<(), _>>(),
+        );
+        assert_eq!(res.await, Err(Error::TimedOut));
+
+        let res = timeout(Duration::from_secs(1), async { Ok::<_, Error>(42) });
+        assert_eq!(res.await, Ok(42));
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_cancelled_connect() {
+        use crate::testing::{ECHO_ELEMENT, ECHO_PATH, ECHO_TRAIT, TestServer};
+
+        let server = TestServer::start().unwrap();
+
+        // each connect is started and then dropped at a different point of the handshake, before its callback has run
+        for micros in 0..50 {
+            let mut connect = pin!(Client::connect(server.address()));
+            assert!(futures::poll!(connect.as_mut()).is_pending());
+
+            std::thread::sleep(Duration::from_micros(micros * 10));
+        }
+
+        let client = Client::connect(server.address()).await.unwrap();
+
+        let reply = client
+            .exec(ECHO_PATH, (ECHO_TRAIT, ECHO_ELEMENT), 42u32)
+            .await
+            .unwrap();
+        assert_eq!(reply.value(), Some(ValueView::UInt32(42)));
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_call() {
+        use crate::testing::{
+            ECHO_ELEMENT, ECHO_PATH, ECHO_TRAIT, FAIL_ELEMENT, FAIL_PATH, FAIL_TRAIT, TestServer,
+        };
+
+        let server = TestServer::start().unwrap();
+        let client = Client::connect(server.address()).await.unwrap();
+
+        assert_eq!(
+            client
+                .call::<u32>(ECHO_PATH, (ECHO_TRAIT, ECHO_ELEMENT), 42u32)
+                .await,
+            Ok(42)
+        );
+
+        assert_eq!(
+            client
+                .call::<()>(
+                    FAIL_PATH,
+                    (FAIL_TRAIT, FAIL_ELEMENT),
+                    Error::PathNotFound.code() as i16
+                )
+                .await,
+            Err(Error::PathNotFound)
+        );
+
+        // codes of the server's own have no library error to map to
+        assert_eq!(
+            client
+                .call::<()>(FAIL_PATH, (FAIL_TRAIT, FAIL_ELEMENT), 1234i16)
+                .await,
+            Err(Error::InvalidData)
+        );
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_inspect_packets() {
+        use crate::testing::{ECHO_ELEMENT, ECHO_PATH, ECHO_TRAIT, TestServer};
+
+        let server = TestServer::start().unwrap();
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+
+        let client = Client::connect_with_args(ClientArgs::new(server.address()).inspect({
+            let seen = seen.clone();
+
+            move |direction, bytes: &[u8]| seen.lock().unwrap().push((direction, bytes.to_vec()))
+        }))
+        .await
+        .unwrap();
+
+        let reply = client
+            .exec(ECHO_PATH, (ECHO_TRAIT, ECHO_ELEMENT), 42u32)
+            .await
+            .unwrap();
+        assert_eq!(reply.value(), Some(ValueView::UInt32(42)));
+
+        let seen = seen.lock().unwrap();
+        let directions = seen.iter().map(|(d, _)| *d).collect::<Vec<_>>();
+
+        assert_eq!(directions, [Direction::Outbound, Direction::Inbound]);
+
+        for (_, bytes) in seen.iter() {
+            let packet = Packet::load(bytes).unwrap();
+
+            assert_eq!(
+                packet.as_message().unwrap().value(),
+                Some(ValueView::UInt32(42))
+            );
+        }
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_inspect_timeout() {
+        use crate::testing::{ECHO_PATH, ECHO_TRAIT, TestServer};
+
+        let server = TestServer::start().unwrap();
+        let client = Client::connect(server.address()).await.unwrap();
+
+        let info = client.inspect_timeout(ECHO_PATH, 5000).await.unwrap();
+        assert_eq!(info.path, ECHO_PATH);
+        assert!(info.traits.contains_key(ECHO_TRAIT));
+
+        assert_eq!(
+            client
+                .inspect_timeout("/dicey/test/missing", 5000)
+                .await
+                .err(),
+            Some(Error::PathNotFound)
+        );
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_exec_raw() {
+        use crate::testing::{FAIL_ELEMENT, FAIL_PATH, FAIL_TRAIT, TestServer};
+
+        let server = TestServer::start().unwrap();
+        let client = Client::connect(server.address()).await.unwrap();
+
+        let reply = client
+            .exec_raw(FAIL_PATH, (FAIL_TRAIT, FAIL_ELEMENT), 1234i16)
+            .await
+            .unwrap();
+
+        assert_eq!(reply.op(), Op::Response);
+        assert!(matches!(
+            reply.value(),
+            Some(ValueView::Error(ErrorMessage { code: 1234, .. }))
+        ));
+
+        let reply = client
+            .request(Op::Exec)
+            .path(FAIL_PATH)
+            .unwrap()
+            .selector((FAIL_TRAIT, FAIL_ELEMENT))
+            .unwrap()
+            .value(Error::PathNotFound.code() as i16)
+            .unwrap()
+            .submit_raw()
+            .await
+            .unwrap();
+
+        assert_eq!(reply.reply_value().err(), Some(Error::PathNotFound));
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_events_with_capacity() {
+        use crate::testing::{SIGNAL_ELEMENT, SIGNAL_PATHS, SIGNAL_TRAIT, TestServer};
+
+        let server = TestServer::start().unwrap();
+        let client = Client::connect(server.address()).await.unwrap();
+
+        let mut source = client.events_with_capacity(1);
+        let mut shared = client.events();
+
+        client
+            .subscribe_to(SIGNAL_PATHS[0], (SIGNAL_TRAIT, SIGNAL_ELEMENT))
+            .await
+            .unwrap();
+
+        for value in [1u32, 2] {
+            server
+                .raise(
+                    MessageBuilder::event()
+                        .unwrap()
+                        .path(SIGNAL_PATHS[0])
+                        .unwrap()
+                        .selector((SIGNAL_TRAIT, SIGNAL_ELEMENT))
+                        .unwrap()
+                        .value(value)
+                        .unwrap()
+                        .build()
+                        .unwrap(),
+                )
+                .unwrap();
+        }
+
+        // the sized queues are fed first, so once the shared one has both events, the source has missed the first one
+        for _ in 0..2 {
+            timeout(Duration::from_secs(5), shared.next())
+                .await
+                .unwrap();
+        }
+
+        let next = timeout(Duration::from_secs(5), async { Ok(source.next().await) });
+        assert_eq!(next.await.unwrap().err(), Some(Error::Overflow));
+
+        let msg = timeout(Duration::from_secs(5), source.next())
+            .await
+            .unwrap();
+        assert_eq!(msg.value(), Some(ValueView::UInt32(2)));
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_subscribe_many() {
+        use crate::{
+            ipc::PartialSubscription,
+            testing::{
+                ECHO_ELEMENT, ECHO_PATH, ECHO_TRAIT, SIGNAL_ELEMENT, SIGNAL_PATHS, SIGNAL_TRAIT,
+                TestServer,
+            },
+        };
+
+        let server = TestServer::start().unwrap();
+        let client = Client::connect(server.address()).await.unwrap();
+
+        let signal = Selector::from((SIGNAL_TRAIT, SIGNAL_ELEMENT));
+
+        let subs = SIGNAL_PATHS[2..]
+            .iter()
+            .map(|&path| (path, signal))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            client.subscribe_many(&subs).await.unwrap(),
+            vec![None; subs.len()]
+        );
+
+        // the client is still in a usable state afterwards
+        let reply = client
+            .exec(ECHO_PATH, (ECHO_TRAIT, ECHO_ELEMENT), 42u32)
+            .await
+            .unwrap();
+        assert_eq!(reply.value(), Some(ValueView::UInt32(42)));
+
+        // a failure doesn't stop the subscriptions after it
+        let err = client
+            .subscribe_many(&[
+                (SIGNAL_PATHS[0], signal),
+                (ECHO_PATH, signal),
+                (SIGNAL_PATHS[1], signal),
+            ])
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            std::error::Error::source(&err)
+                .and_then(|source| source.downcast_ref::<PartialSubscription>()),
+            Some(&PartialSubscription {
+                subscribed: vec![0, 2],
+                failed: vec![1],
+            })
+        );
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_subscribe_nul() {
+        use crate::testing::{SIGNAL_ELEMENT, SIGNAL_PATHS, SIGNAL_TRAIT, TestServer};
+
+        let server = TestServer::start().unwrap();
+        let client = Client::connect(server.address()).await.unwrap();
+
+        // a single trailing NUL is the same as none
+        let path = format!("{}\0", SIGNAL_PATHS[1]);
+        let tname = format!("{SIGNAL_TRAIT}\0");
+        let elem = format!("{SIGNAL_ELEMENT}\0");
+
+        assert_eq!(
+            client
+                .subscribe_to(SIGNAL_PATHS[0], (SIGNAL_TRAIT, SIGNAL_ELEMENT))
+                .await,
+            Ok(())
+        );
+        assert_eq!(
+            client
+                .subscribe_to(path, (tname.as_str(), elem.as_str()))
+                .await,
+            Ok(())
+        );
+
+        assert_eq!(
+            client
+                .subscribe_to(SIGNAL_PATHS[2], ("dicey.test\0.Signal", SIGNAL_ELEMENT))
+                .await,
+            Err(Error::InvalidData)
+        );
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_subscribe_stream() {
+        use crate::testing::{SIGNAL_ELEMENT, SIGNAL_PATHS, SIGNAL_TRAIT, TestServer};
+
+        let server = TestServer::start().unwrap();
+        let client = Client::connect(server.address()).await.unwrap();
+
+        let raise = |path| {
+            server
+                .raise(
+                    MessageBuilder::event()
+                        .unwrap()
+                        .path(path)
+                        .unwrap()
+                        .selector((SIGNAL_TRAIT, SIGNAL_ELEMENT))
+                        .unwrap()
+                        .value(())
+                        .unwrap()
+                        .build()
+                        .unwrap(),
+                )
+                .unwrap()
+        };
+
+        // the NUL-terminated forms are the same as the plain ones, both for filtering and for unsubscribing on drop
+        let path = format!("{}\0", SIGNAL_PATHS[0]);
+        let tname = format!("{SIGNAL_TRAIT}\0");
+        let elem = format!("{SIGNAL_ELEMENT}\0");
+
+        let mut sub = client
+            .subscribe_stream(path, (tname.as_str(), elem.as_str()))
+            .await
+            .unwrap();
+
+        raise(SIGNAL_PATHS[1]);
+        raise(SIGNAL_PATHS[0]);
+
+        let msg = timeout(Duration::from_secs(5), async { Ok(sub.next().await) })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(msg.path(), SIGNAL_PATHS[0]);
+
+        drop(sub);
+
+        // the unsubscribe is fired and forgotten, so give it time to reach the server
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let mut shared = client.events();
+
+        client
+            .subscribe_to(SIGNAL_PATHS[1], (SIGNAL_TRAIT, SIGNAL_ELEMENT))
+            .await
+            .unwrap();
+
+        raise(SIGNAL_PATHS[0]);
+        raise(SIGNAL_PATHS[1]);
+
+        let msg = timeout(Duration::from_secs(5), shared.next())
+            .await
+            .unwrap();
+        assert_eq!(msg.path(), SIGNAL_PATHS[1]);
+    }
 }
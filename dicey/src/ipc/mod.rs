@@ -14,18 +14,131 @@
  * limitations under the License.
  */
 
+use std::fmt;
+
+use crate::{DiceyError, Error};
+
 pub mod blocking;
 
 mod address;
 mod object_info;
 
-pub use address::Address;
-pub use object_info::{Element, Elements, ObjectInfo, Operation, Property, Signal, Traits};
+pub use address::{Address, Transport, supported_transports};
+pub use object_info::{
+    ChangedElement, Element, Elements, MalformedEntry, MergePolicy, ObjectInfo, Operation,
+    Property, SchemaDiff, Signal, Traits,
+};
 
 #[cfg(feature = "async")]
 mod client;
 
 #[cfg(feature = "async")]
-pub use client::{Client, RequestBuilder, SignalSource};
+mod pool;
+
+#[cfg(feature = "async")]
+mod stats;
+
+#[cfg(feature = "async")]
+pub use client::{
+    Client, ClientArgs, EventSink, PropertyHandle, RequestBuilder, SignalSource, Subscription,
+    timeout,
+};
+
+#[cfg(feature = "async")]
+pub use pool::ClientPool;
+
+#[cfg(feature = "async")]
+pub use stats::ClientStats;
 
 pub const DEFAULT_TIMEOUT_MS: u32 = 1000u32;
+
+// libdicey stores timeouts as signed 32-bit integers and treats -1 as "wait forever"
+pub const NO_TIMEOUT: u32 = u32::MAX;
+
+// which way a packet handed to an Inspector is going
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+/// Receives every message a client sends or receives, serialized as it is on the wire.
+///
+/// Inbound packets come from the client's event loop, outbound ones from whichever thread sent the request. Outbound
+/// packets are reported before libdicey stamps them with their sequence number, and messages libdicey builds on its own,
+/// such as the ones sent by `subscribe_to`, as well as handshake packets, are not reported at all.
+pub type Inspector = Box<dyn FnMut(Direction, &[u8]) + Send>;
+
+// the source of the error returned by subscribe_many when some of the subscriptions fail. Both lists hold indices into
+// the slice that was passed in, so that the subscriptions that went through can be undone
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PartialSubscription {
+    pub subscribed: Vec<usize>,
+    pub failed: Vec<usize>,
+}
+
+impl fmt::Display for PartialSubscription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "subscribed to {} of {} selectors",
+            self.subscribed.len(),
+            self.subscribed.len() + self.failed.len()
+        )
+    }
+}
+
+impl std::error::Error for PartialSubscription {}
+
+// the error is the one of the first subscription that failed, in the order they were given
+fn collect_subscriptions<T>(results: Vec<Result<T, Error>>) -> Result<Vec<T>, DiceyError> {
+    if results.iter().all(Result::is_ok) {
+        return Ok(results.into_iter().flatten().collect());
+    }
+
+    let mut first_err = None;
+    let mut partial = PartialSubscription {
+        subscribed: Vec::new(),
+        failed: Vec::new(),
+    };
+
+    for (i, res) in results.into_iter().enumerate() {
+        match res {
+            Ok(_) => partial.subscribed.push(i),
+            Err(err) => {
+                first_err.get_or_insert(err);
+                partial.failed.push(i);
+            }
+        }
+    }
+
+    Err(DiceyError::with_source(first_err.unwrap(), partial))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect_subscriptions() {
+        assert_eq!(collect_subscriptions(vec![Ok(1), Ok(2)]).unwrap(), [1, 2]);
+
+        let err = collect_subscriptions(vec![
+            Ok(()),
+            Err(Error::TraitNotFound),
+            Ok(()),
+            Err(Error::TimedOut),
+        ])
+        .unwrap_err();
+
+        assert_eq!(err.kind(), Error::TraitNotFound);
+        assert_eq!(
+            std::error::Error::source(&err)
+                .and_then(|source| source.downcast_ref::<PartialSubscription>()),
+            Some(&PartialSubscription {
+                subscribed: vec![0, 2],
+                failed: vec![1, 3],
+            })
+        );
+    }
+}
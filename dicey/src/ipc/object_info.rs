@@ -14,16 +14,22 @@
  * limitations under the License.
  */
 
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap, hash_map},
+    fmt, iter,
+};
 
 use dicey_sys::{
     dicey_element_type, dicey_element_type_DICEY_ELEMENT_TYPE_OPERATION,
     dicey_element_type_DICEY_ELEMENT_TYPE_PROPERTY, dicey_element_type_DICEY_ELEMENT_TYPE_SIGNAL,
 };
 
-use crate::{Error, FromDicey, ValueView};
+use crate::{
+    Byte, DiceyError, Error, FromDicey, Message, Selector, Signature, ToDicey, Tuple2, Type,
+    ValueBuilder, ValueView,
+};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Eq, PartialEq)]
 pub struct ObjectInfo {
     pub path: String,
     pub traits: Traits,
@@ -40,19 +46,184 @@ impl ObjectInfo {
     ) -> Result<Self, crate::Error> {
         Ok(Self::new(path, Traits::from_dicey(value)?))
     }
+
+    /// Parses introspection data into an object, like the clients' `inspect` methods do.
+    ///
+    /// Elements of a kind this crate doesn't know, such as the ones a newer server may define, are kept as
+    /// [`Element::Unknown`]. If a trait or element is malformed, the returned error has a [`MalformedEntry`] source
+    /// naming it.
+    pub fn parse(path: impl Into<String>, value: &ValueView<'_>) -> Result<Self, DiceyError> {
+        Ok(Self::new(path.into(), parse_traits(value, false)?))
+    }
+
+    // same as parse, but unknown element kinds are malformed too; for validating introspection data
+    pub fn parse_strict(
+        path: impl Into<String>,
+        value: &ValueView<'_>,
+    ) -> Result<Self, DiceyError> {
+        Ok(Self::new(path.into(), parse_traits(value, true)?))
+    }
+
+    /// Lists what changed going from this object's schema to `other`'s; the paths are not compared.
+    ///
+    /// Signatures are compared once parsed, so that spelling differences such as whitespace don't count as changes.
+    /// The elements of traits that were added or removed as a whole are not listed individually.
+    pub fn diff(&self, other: &ObjectInfo) -> SchemaDiff {
+        let mut diff = SchemaDiff::default();
+
+        for (tname, elements) in &self.traits {
+            let Some(new_elements) = other.traits.get(tname) else {
+                diff.removed_traits.push(tname.clone());
+                continue;
+            };
+
+            for (ename, element) in elements {
+                match new_elements.get(ename) {
+                    None => diff.removed_elements.push((tname.clone(), ename.clone())),
+                    Some(new) if !same_element(element, new) => {
+                        diff.changed_elements.push(ChangedElement {
+                            trait_name: tname.clone(),
+                            element: ename.clone(),
+                            old: element.clone(),
+                            new: new.clone(),
+                        })
+                    }
+                    Some(_) => {}
+                }
+            }
+
+            diff.added_elements.extend(
+                new_elements
+                    .elements()
+                    .filter(|(ename, _)| elements.get(ename).is_none())
+                    .map(|(ename, _)| (tname.clone(), ename.clone())),
+            );
+        }
+
+        diff.added_traits.extend(
+            other
+                .traits
+                .keys()
+                .filter(|tname| !self.traits.contains_key(*tname))
+                .cloned(),
+        );
+
+        diff.sort();
+
+        diff
+    }
+
+    pub fn element(&self, trait_name: &str, elem: &str) -> Option<&Element> {
+        self.traits.get(trait_name)?.get(elem)
+    }
+
+    // true if clients written against `older` keep working with this object: nothing was removed, and the only change
+    // allowed to existing elements is making a read-only property writable
+    pub fn is_backward_compatible(&self, older: &ObjectInfo) -> bool {
+        let diff = older.diff(self);
+
+        diff.removed_traits.is_empty()
+            && diff.removed_elements.is_empty()
+            && diff
+                .changed_elements
+                .iter()
+                .all(ChangedElement::is_compatible)
+    }
+
+    /// Adds the traits and elements of `other` to this object, keeping the current path.
+    ///
+    /// Elements defined by both objects with a different kind or signature are handled according to `policy`; with
+    /// [`MergePolicy::Strict`] nothing is merged if there is any conflict, and [`Error::SignatureMismatch`] is returned.
+    pub fn merge(&mut self, other: ObjectInfo, policy: MergePolicy) -> Result<(), Error> {
+        if policy == MergePolicy::Strict {
+            for (tname, elements) in &other.traits {
+                if let Some(current) = self.traits.get(tname) {
+                    current.check_conflicts(elements)?;
+                }
+            }
+        }
+
+        for (tname, elements) in other.traits {
+            self.traits.entry(tname).or_default().0.extend(elements.0);
+        }
+
+        Ok(())
+    }
+
+    // traits ordered by name, for output that must be stable across runs
+    pub fn sorted_traits(&self) -> BTreeMap<&str, &Elements> {
+        self.traits
+            .iter()
+            .map(|(name, elements)| (name.as_str(), elements))
+            .collect()
+    }
+
+    pub(crate) fn is_property_writable(&self, sel: Selector<'_>) -> Result<bool, Error> {
+        let trait_name = std::str::from_utf8(sel.trait_name).map_err(|_| Error::InvalidData)?;
+        let elem = std::str::from_utf8(sel.elem).map_err(|_| Error::InvalidData)?;
+
+        match self.element(trait_name, elem) {
+            Some(Element::Property(prop)) => Ok(!prop.readonly),
+            Some(_) => Err(Error::ValueTypeMismatch),
+            None => Err(Error::ElementNotFound),
+        }
+    }
 }
 
-#[derive(Clone, Debug)]
+// encodes the traits the same way the introspection data property does, so that the result can be parsed back
+impl ToDicey for ObjectInfo {
+    const TYPE_KIND: Type = Type::Array;
+
+    fn to_dicey(&self, builder: &mut ValueBuilder) -> Result<(), Error> {
+        builder.set(
+            self.traits
+                .iter()
+                .map(|(name, elements)| (name.as_str(), elements))
+                .collect::<Vec<_>>(),
+        )
+    }
+}
+
+// printed in name order, so that the output is stable
+impl fmt::Debug for ObjectInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ObjectInfo")
+            .field("path", &self.path)
+            .field("traits", &self.sorted_traits())
+            .finish()
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Element {
     Operation(Operation),
     Property(Property),
     Signal(Signal),
+
+    // an element kind this crate doesn't know about, kept as-is so that the rest of the object stays usable
+    Unknown { kind: u8, signature: String },
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Default, Eq, PartialEq)]
 pub struct Elements(HashMap<String, Element>);
 
 impl Elements {
+    fn check_conflicts(&self, other: &Elements) -> Result<(), Error> {
+        let conflicting = other
+            .elements()
+            .any(|(name, element)| self.get(name).is_some_and(|current| current != element));
+
+        if conflicting {
+            Err(Error::SignatureMismatch)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Element> {
+        self.0.get(name)
+    }
+
     pub fn elements(&self) -> impl Iterator<Item = (&String, &Element)> {
         self.0.iter()
     }
@@ -77,6 +248,13 @@ impl Elements {
         })
     }
 
+    // same as elements(), but ordered by name
+    pub fn sorted(&self) -> BTreeMap<&str, &Element> {
+        self.elements()
+            .map(|(name, element)| (name.as_str(), element))
+            .collect()
+    }
+
     pub fn signals(&self) -> impl Iterator<Item = (&String, &Signal)> {
         self.elements().filter_map(|(name, element)| {
             if let Element::Signal(signal) = element {
@@ -88,87 +266,555 @@ impl Elements {
     }
 }
 
-#[derive(Clone, Debug)]
+impl FromIterator<(String, Element)> for Elements {
+    fn from_iter<I: IntoIterator<Item = (String, Element)>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl IntoIterator for Elements {
+    type Item = (String, Element);
+    type IntoIter = hash_map::IntoIter<String, Element>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Elements {
+    type Item = (&'a String, &'a Element);
+    type IntoIter = hash_map::Iter<'a, String, Element>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl fmt::Debug for Elements {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Elements").field(&self.sorted()).finish()
+    }
+}
+
+// the inverse of parse_element: operations and signals are (kind, signature) tuples, while properties also carry their
+// readonly flag
+impl ToDicey for &Element {
+    const TYPE_KIND: Type = Type::Tuple;
+
+    fn to_dicey(&self, builder: &mut ValueBuilder) -> Result<(), Error> {
+        let kind = |kind: dicey_element_type| Byte(kind as u8);
+
+        match self {
+            Element::Operation(op) => builder.set(Tuple2(
+                kind(dicey_element_type_DICEY_ELEMENT_TYPE_OPERATION),
+                op.signature.as_str(),
+            )),
+            Element::Property(prop) => builder.set((
+                kind(dicey_element_type_DICEY_ELEMENT_TYPE_PROPERTY),
+                prop.signature.as_str(),
+                prop.readonly,
+            )),
+            Element::Signal(signal) => builder.set(Tuple2(
+                kind(dicey_element_type_DICEY_ELEMENT_TYPE_SIGNAL),
+                signal.signature.as_str(),
+            )),
+            Element::Unknown { kind, signature } => {
+                builder.set(Tuple2(Byte(*kind), signature.as_str()))
+            }
+        }
+    }
+}
+
+impl ToDicey for Element {
+    const TYPE_KIND: Type = <&Element as ToDicey>::TYPE_KIND;
+
+    fn to_dicey(&self, builder: &mut ValueBuilder) -> Result<(), Error> {
+        (&self).to_dicey(builder)
+    }
+}
+
+impl ToDicey for &Elements {
+    const TYPE_KIND: Type = Type::Array;
+
+    fn to_dicey(&self, builder: &mut ValueBuilder) -> Result<(), Error> {
+        builder.set(
+            self.elements()
+                .map(|(name, element)| (name.as_str(), element))
+                .collect::<Vec<_>>(),
+        )
+    }
+}
+
+impl ToDicey for Elements {
+    const TYPE_KIND: Type = <&Elements as ToDicey>::TYPE_KIND;
+
+    fn to_dicey(&self, builder: &mut ValueBuilder) -> Result<(), Error> {
+        (&self).to_dicey(builder)
+    }
+}
+
+/// The differences between two schemas, as found by [`ObjectInfo::diff`].
+///
+/// Elements are named by `(trait, element)` pairs, and every list is sorted by name.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SchemaDiff {
+    pub added_traits: Vec<String>,
+    pub removed_traits: Vec<String>,
+
+    pub added_elements: Vec<(String, String)>,
+    pub removed_elements: Vec<(String, String)>,
+    pub changed_elements: Vec<ChangedElement>,
+}
+
+impl SchemaDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_traits.is_empty()
+            && self.removed_traits.is_empty()
+            && self.added_elements.is_empty()
+            && self.removed_elements.is_empty()
+            && self.changed_elements.is_empty()
+    }
+
+    fn sort(&mut self) {
+        self.added_traits.sort();
+        self.removed_traits.sort();
+        self.added_elements.sort();
+        self.removed_elements.sort();
+        self.changed_elements
+            .sort_by(|a, b| (&a.trait_name, &a.element).cmp(&(&b.trait_name, &b.element)));
+    }
+}
+
+// an element whose kind, signature or writability differs between the two schemas
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChangedElement {
+    pub trait_name: String,
+    pub element: String,
+
+    pub old: Element,
+    pub new: Element,
+}
+
+impl ChangedElement {
+    fn is_compatible(&self) -> bool {
+        match (&self.old, &self.new) {
+            (Element::Property(old), Element::Property(new)) => {
+                same_signature(&old.signature, &new.signature) && (old.readonly || !new.readonly)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn same_element(a: &Element, b: &Element) -> bool {
+    match (a, b) {
+        (Element::Operation(a), Element::Operation(b)) => {
+            same_signature(&a.signature, &b.signature)
+        }
+        (Element::Property(a), Element::Property(b)) => {
+            a.readonly == b.readonly && same_signature(&a.signature, &b.signature)
+        }
+        (Element::Signal(a), Element::Signal(b)) => same_signature(&a.signature, &b.signature),
+        (
+            Element::Unknown {
+                kind: a_kind,
+                signature: a_sig,
+            },
+            Element::Unknown {
+                kind: b_kind,
+                signature: b_sig,
+            },
+        ) => a_kind == b_kind && same_signature(a_sig, b_sig),
+        _ => false,
+    }
+}
+
+// signatures that don't parse can still be compared as written
+fn same_signature(a: &str, b: &str) -> bool {
+    match (Signature::parse(a), Signature::parse(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+// how ObjectInfo::merge deals with an element both objects define differently
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum MergePolicy {
+    // fail the whole merge
+    #[default]
+    Strict,
+
+    // the element being merged in replaces the current one
+    LastWins,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Operation {
     pub signature: String,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Property {
     pub signature: String,
     pub readonly: bool,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Signal {
     pub signature: String,
 }
 
+// a plain map, so it can already be iterated over and extended like any other collection
 pub type Traits = HashMap<String, Elements>;
 
 impl<'a> FromDicey<'a> for Traits {
     fn from_dicey(value: &ValueView<'a>) -> Result<Self, Error> {
-        // let list = if let ValueView::Array { items, .. } = value {
-        //     items
-        // } else {
-        //     return Err(crate::Error::ValueTypeMismatch);
-        // };
+        parse_traits(value, false).map_err(Error::from)
+    }
+}
+
+/// The introspection entry that failed to parse, attached as the source of the error returned by
+/// [`ObjectInfo::parse`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MalformedEntry {
+    pub trait_name: String,
+
+    // None if the trait itself is malformed
+    pub element: Option<String>,
+}
+
+impl fmt::Display for MalformedEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.element {
+            Some(elem) => write!(f, "malformed element `{}:{elem}`", self.trait_name),
+            None => write!(f, "malformed trait `{}`", self.trait_name),
+        }
+    }
+}
+
+impl std::error::Error for MalformedEntry {}
+
+fn parse_traits(value: &ValueView<'_>, strict: bool) -> Result<Traits, DiceyError> {
+    let malformed = |kind, trait_name: &str, element: Option<&str>| {
+        DiceyError::with_source(
+            kind,
+            MalformedEntry {
+                trait_name: trait_name.to_string(),
+                element: element.map(str::to_string),
+            },
+        )
+    };
+
+    value
+        .extract::<Vec<(&str, ValueView<'_>)>>()?
+        .into_iter()
+        .map(|(tname, entries)| {
+            let entries = entries
+                .extract::<Vec<(&str, ValueView<'_>)>>()
+                .map_err(|kind| malformed(kind, tname, None))?
+                .into_iter()
+                .map(|(ename, value)| {
+                    parse_element(&value, strict)
+                        .map(|element| (ename.to_string(), element))
+                        .map_err(|kind| malformed(kind, tname, Some(ename)))
+                })
+                .collect::<Result<_, _>>()?;
+
+            Ok((tname.to_string(), Elements(entries)))
+        })
+        .collect()
+}
+
+// only looks at the top level of the introspection data, leaving the element lists unparsed
+// only the names are decoded, the elements of each trait are skipped over
+pub(crate) fn parse_trait_names(reply: &Message) -> Result<Vec<String>, Error> {
+    let mut traits = match reply.value_array_iter() {
+        Ok(traits) => traits,
+        Err(err) => {
+            // error replies aren't arrays, and fail with their own error instead
+            reply.reply_value()?;
+
+            return Err(err);
+        }
+    };
+
+    iter::from_fn(|| traits.next_pair_first())
+        .map(|tname| Ok(tname?.extract::<&str>()?.to_string()))
+        .collect()
+}
+
+fn parse_element(value: &ValueView<'_>, strict: bool) -> Result<Element, Error> {
+    let entries = if let ValueView::Tuple(entries) = value {
+        entries
+    } else {
+        return Err(Error::ValueTypeMismatch);
+    };
+
+    let (kind, sig, readonly) = match &entries[..] {
+        [
+            ValueView::Byte(kind),
+            ValueView::String(sig),
+            ValueView::Bool(ro),
+        ] => (kind, sig, *ro),
+        [ValueView::Byte(kind), ValueView::String(sig)] => (kind, sig, false),
+        _ => return Err(Error::ValueTypeMismatch),
+    };
+
+    match kind.0 as dicey_element_type {
+        dicey_element_type_DICEY_ELEMENT_TYPE_OPERATION => Ok(Element::Operation(Operation {
+            signature: sig.to_string(),
+        })),
+        dicey_element_type_DICEY_ELEMENT_TYPE_PROPERTY => Ok(Element::Property(Property {
+            signature: sig.to_string(),
+            readonly,
+        })),
+        dicey_element_type_DICEY_ELEMENT_TYPE_SIGNAL => Ok(Element::Signal(Signal {
+            signature: sig.to_string(),
+        })),
+        _ if strict => Err(Error::BadMessage),
+        _ => Ok(Element::Unknown {
+            kind: kind.0,
+            signature: sig.to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sorted() {
+        let elements = Elements(
+            ["c", "a", "b"]
+                .into_iter()
+                .map(|name| {
+                    (
+                        name.to_string(),
+                        Element::Signal(Signal {
+                            signature: "$".to_string(),
+                        }),
+                    )
+                })
+                .collect(),
+        );
+
+        assert_eq!(
+            elements.sorted().into_keys().collect::<Vec<_>>(),
+            ["a", "b", "c"]
+        );
+
+        let mut names: Vec<_> = (&elements)
+            .into_iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["a", "b", "c"]);
 
-        // let mut traits = Traits::with_capacity(list.len());
+        let copy: Elements = elements.clone().into_iter().collect();
+        assert_eq!(copy.sorted(), elements.sorted());
 
-        // for trait_def in list {
+        let info = ObjectInfo::new(
+            "/foo".to_string(),
+            [("z.Z", elements.clone()), ("a.A", elements)]
+                .into_iter()
+                .map(|(name, elements)| (name.to_string(), elements))
+                .collect(),
+        );
 
-        // }
+        assert_eq!(
+            info.sorted_traits().into_keys().collect::<Vec<_>>(),
+            ["a.A", "z.Z"]
+        );
+    }
 
-        value
-            .extract::<Vec<(&str, Vec<(&str, ValueView<'a>)>)>>()?
+    fn object_with(tname: &str, ename: &str, signature: &str) -> ObjectInfo {
+        let elements = Elements(
+            [(
+                ename.to_string(),
+                Element::Operation(Operation {
+                    signature: signature.to_string(),
+                }),
+            )]
             .into_iter()
-            .map(|(tname, entries)| {
-                let entries = entries
-                    .into_iter()
-                    .map(|(ename, value)| {
-                        let entries = if let ValueView::Tuple(entries) = value {
-                            entries
-                        } else {
-                            return Err(crate::Error::ValueTypeMismatch);
-                        };
-
-                        let (kind, sig, readonly) = match &entries[..] {
-                            [
-                                ValueView::Byte(kind),
-                                ValueView::String(sig),
-                                ValueView::Bool(ro),
-                            ] => (kind, sig, *ro),
-                            [ValueView::Byte(kind), ValueView::String(sig)] => (kind, sig, false),
-                            _ => return Err(crate::Error::ValueTypeMismatch),
-                        };
-
-                        let element = match kind.0 as dicey_element_type {
-                            dicey_element_type_DICEY_ELEMENT_TYPE_OPERATION => {
-                                Element::Operation(Operation {
-                                    signature: sig.to_string(),
-                                })
-                            }
-                            dicey_element_type_DICEY_ELEMENT_TYPE_PROPERTY => {
-                                Element::Property(Property {
-                                    signature: sig.to_string(),
-                                    readonly,
-                                })
-                            }
-                            dicey_element_type_DICEY_ELEMENT_TYPE_SIGNAL => {
-                                Element::Signal(Signal {
-                                    signature: sig.to_string(),
-                                })
-                            }
-                            _ => return Err(Error::BadMessage),
-                        };
-
-                        Ok((ename.to_string(), element))
-                    })
-                    .collect::<Result<_, _>>()?;
-
-                Ok((tname.to_string(), Elements(entries)))
+            .collect(),
+        );
+
+        ObjectInfo::new(
+            "/foo".to_string(),
+            [(tname.to_string(), elements)].into_iter().collect(),
+        )
+    }
+
+    #[test]
+    fn test_parse_reports_entry() {
+        let msg = crate::MessageBuilder::response()
+            .unwrap()
+            .path("/foo")
+            .unwrap()
+            .selector(("a.Trait", "Prop"))
+            .unwrap()
+            .value(vec![(
+                "a.A",
+                vec![
+                    ("Good", crate::Tuple2(crate::Byte(b'O'), "$ -> $")),
+                    ("Bad", crate::Tuple2(crate::Byte(b'?'), "$")),
+                ],
+            )])
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let err = ObjectInfo::parse_strict("/foo", &msg.value().unwrap()).unwrap_err();
+        assert_eq!(err.kind(), Error::BadMessage);
+
+        let entry = std::error::Error::source(&err)
+            .and_then(|source| source.downcast_ref::<MalformedEntry>())
+            .unwrap();
+        assert_eq!(entry.trait_name, "a.A");
+        assert_eq!(entry.element.as_deref(), Some("Bad"));
+
+        // a lenient parse keeps both, without making sense of the unknown one
+        let info = ObjectInfo::parse("/foo", &msg.value().unwrap()).unwrap();
+        assert!(matches!(
+            info.element("a.A", "Good"),
+            Some(Element::Operation(_))
+        ));
+        assert_eq!(
+            info.element("a.A", "Bad"),
+            Some(&Element::Unknown {
+                kind: b'?',
+                signature: "$".to_string(),
             })
-            .collect()
+        );
+
+        // element lists are not looked at when only asking for trait names
+        assert_eq!(parse_trait_names(&msg).unwrap(), ["a.A"]);
+    }
+
+    #[test]
+    fn test_to_dicey_roundtrip() {
+        let mut info = object_with("a.A", "Op", "s -> s");
+
+        let elements = [
+            (
+                "Prop",
+                Element::Property(Property {
+                    signature: "u".to_string(),
+                    readonly: true,
+                }),
+            ),
+            (
+                "Sig",
+                Element::Signal(Signal {
+                    signature: "$".to_string(),
+                }),
+            ),
+        ]
+        .into_iter()
+        .map(|(name, element)| (name.to_string(), element))
+        .collect();
+
+        info.traits.insert("b.B".to_string(), elements);
+
+        let msg = crate::MessageBuilder::response()
+            .unwrap()
+            .path("/foo")
+            .unwrap()
+            .selector(("a.Trait", "Prop"))
+            .unwrap()
+            .value(info.clone())
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            ObjectInfo::parse("/foo", &msg.value().unwrap()).unwrap(),
+            info
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut old = object_with("a.A", "Op", "s -> s");
+        old.merge(object_with("b.B", "Op", "$ -> $"), MergePolicy::Strict)
+            .unwrap();
+        old.traits.get_mut("a.A").unwrap().0.insert(
+            "Prop".to_string(),
+            Element::Property(Property {
+                signature: "u".to_string(),
+                readonly: true,
+            }),
+        );
+
+        assert!(old.diff(&old.clone()).is_empty());
+        assert!(old.is_backward_compatible(&old));
+
+        // a writable property, a new element and a new trait don't break older clients
+        let mut new = old.clone();
+        new.traits.remove("b.B");
+        new.merge(object_with("c.C", "Op", "$ -> $"), MergePolicy::Strict)
+            .unwrap();
+        new.merge(object_with("a.A", "New", "$ -> $"), MergePolicy::Strict)
+            .unwrap();
+        new.traits.get_mut("a.A").unwrap().0.insert(
+            "Prop".to_string(),
+            Element::Property(Property {
+                signature: "u".to_string(),
+                readonly: false,
+            }),
+        );
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.added_traits, ["c.C"]);
+        assert_eq!(diff.removed_traits, ["b.B"]);
+        assert_eq!(
+            diff.added_elements,
+            [("a.A".to_string(), "New".to_string())]
+        );
+        assert!(diff.removed_elements.is_empty());
+        assert_eq!(diff.changed_elements.len(), 1);
+        assert_eq!(diff.changed_elements[0].element, "Prop");
+
+        // b.B is gone
+        assert!(!new.is_backward_compatible(&old));
+        new.merge(object_with("b.B", "Op", "$ -> $"), MergePolicy::Strict)
+            .unwrap();
+        assert!(new.is_backward_compatible(&old));
+
+        // but not the other way around, as "New" and writability would be lost
+        assert!(!old.is_backward_compatible(&new));
+
+        // changing an operation's signature breaks compatibility
+        new.merge(object_with("a.A", "Op", "s -> u"), MergePolicy::LastWins)
+            .unwrap();
+        assert!(!new.is_backward_compatible(&old));
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut info = object_with("a.A", "Op", "s -> s");
+
+        info.merge(object_with("a.A", "Other", "$ -> $"), MergePolicy::Strict)
+            .unwrap();
+        info.merge(object_with("b.B", "Op", "$ -> $"), MergePolicy::Strict)
+            .unwrap();
+
+        assert_eq!(info.traits.len(), 2);
+        assert!(info.element("a.A", "Other").is_some());
+
+        assert_eq!(
+            info.merge(object_with("a.A", "Op", "$ -> $"), MergePolicy::Strict),
+            Err(Error::SignatureMismatch)
+        );
+        assert!(
+            matches!(info.element("a.A", "Op"), Some(Element::Operation(op)) if op.signature == "s -> s")
+        );
+
+        info.merge(object_with("a.A", "Op", "$ -> $"), MergePolicy::LastWins)
+            .unwrap();
+        assert!(
+            matches!(info.element("a.A", "Op"), Some(Element::Operation(op)) if op.signature == "$ -> $")
+        );
     }
 }
@@ -0,0 +1,225 @@
+/*
+ * Copyright (c) 2014-2024 Zuru Tech HK Limited, All rights reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    ffi::CString,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use tokio::sync::Mutex;
+
+use crate::{Error, Message, Selector, ToDicey};
+
+use super::{
+    address::Address,
+    client::{Client, ClientArgs, EventSink},
+};
+
+/// A fixed number of async clients connected to the same server, handed out in turn.
+///
+/// A single [`Client`] already pipelines its requests, so a pool only pays off when one connection is the bottleneck;
+/// a handful of clients is usually plenty. Clients don't reconnect on their own: when [`ClientPool::acquire`] lands on
+/// one whose connection was lost, it connects a new one in its place. Whoever still holds the old client keeps it,
+/// together with its subscriptions and event sources, which are not carried over to the replacement. Subscribe
+/// through a dedicated client if events must survive reconnects.
+pub struct ClientPool {
+    addr: CString,
+    event_queue_size: usize,
+    event_sink: EventSink,
+
+    slots: Vec<Mutex<Option<Arc<Client>>>>,
+    next: AtomicUsize,
+}
+
+impl ClientPool {
+    pub async fn connect(pipe: impl Into<Address>, size: usize) -> Result<Self, Error> {
+        Self::connect_with_args(ClientArgs::new(pipe), size).await
+    }
+
+    // all clients connect upfront, so that an unreachable server is reported here and not on first use
+    pub async fn connect_with_args<A: Into<Address>>(
+        args: ClientArgs<A>,
+        size: usize,
+    ) -> Result<Self, Error> {
+        if size == 0 {
+            return Err(Error::InvalidData);
+        }
+
+        let pool = ClientPool {
+            addr: args.pipe.into().as_c_str().to_owned(),
+            event_queue_size: args.event_queue_size,
+            event_sink: args.event_sink,
+            slots: (0..size).map(|_| Mutex::new(None)).collect(),
+            next: AtomicUsize::new(0),
+        };
+
+        for slot in &pool.slots {
+            *slot.lock().await = Some(Arc::new(pool.connect_one().await?));
+        }
+
+        Ok(pool)
+    }
+
+    // the client is shared with other callers; dropping it gives it back
+    pub async fn acquire(&self) -> Result<Arc<Client>, Error> {
+        let n = self.next.fetch_add(1, Ordering::Relaxed) % self.slots.len();
+
+        let mut slot = self.slots[n].lock().await;
+
+        if let Some(client) = slot.as_ref()
+            && client.ready().await.is_ok()
+        {
+            return Ok(client.clone());
+        }
+
+        // a failed attempt leaves the slot empty, to be retried by the next caller landing on it
+        *slot = None;
+
+        let client = Arc::new(self.connect_one().await?);
+
+        *slot = Some(client.clone());
+
+        Ok(client)
+    }
+
+    pub async fn exec<'b>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+        argument: impl ToDicey,
+    ) -> Result<Message, Error> {
+        self.acquire().await?.exec(path, selector, argument).await
+    }
+
+    pub async fn get<'b>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+    ) -> Result<Message, Error> {
+        self.acquire().await?.get(path, selector).await
+    }
+
+    pub async fn set<'b>(
+        &self,
+        path: impl Into<Vec<u8>>,
+        selector: impl Into<Selector<'b>>,
+        argument: impl ToDicey,
+    ) -> Result<(), Error> {
+        self.acquire().await?.set(path, selector, argument).await
+    }
+
+    pub fn size(&self) -> usize {
+        self.slots.len()
+    }
+
+    async fn connect_one(&self) -> Result<Client, Error> {
+        Client::connect_with_args(
+            ClientArgs::new(self.addr.as_c_str())
+                .event_queue_size(self.event_queue_size)
+                .event_sink(self.event_sink),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_empty_pool() {
+        let res = ClientPool::connect("/tmp/dicey-test.sock", 0).await;
+
+        assert!(matches!(res, Err(Error::InvalidData)));
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_acquire_round_robin() {
+        use crate::{
+            ValueView,
+            testing::{ECHO_ELEMENT, ECHO_PATH, ECHO_TRAIT, TestServer},
+        };
+
+        let server = TestServer::start().unwrap();
+        let pool = ClientPool::connect(server.address(), 3).await.unwrap();
+
+        let mut clients = Vec::new();
+        for _ in 0..6 {
+            clients.push(pool.acquire().await.unwrap());
+        }
+
+        // every slot is handed out once per lap, in the same order
+        for (i, client) in clients.iter().enumerate() {
+            for (j, other) in clients.iter().enumerate() {
+                assert_eq!(Arc::ptr_eq(client, other), i % 3 == j % 3);
+            }
+        }
+
+        let reply = pool
+            .exec(ECHO_PATH, (ECHO_TRAIT, ECHO_ELEMENT), 42u32)
+            .await
+            .unwrap();
+        assert_eq!(reply.value(), Some(ValueView::UInt32(42)));
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_reconnect() {
+        use std::time::Duration;
+
+        use crate::{
+            ValueView,
+            testing::{ECHO_ELEMENT, ECHO_PATH, ECHO_TRAIT, TestServer},
+        };
+
+        let server = TestServer::start().unwrap();
+        let pool = ClientPool::connect(server.address(), 2).await.unwrap();
+
+        let old = [pool.acquire().await.unwrap(), pool.acquire().await.unwrap()];
+
+        let _server = server.restart().unwrap();
+
+        // the clients only find out about the lost connection once libdicey notices it
+        for client in &old {
+            for _ in 0..100 {
+                if client.ready().await.is_err() {
+                    break;
+                }
+
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+
+            assert_eq!(client.ready().await, Err(Error::ConnectionReset));
+        }
+
+        for client in &old {
+            let new = pool.acquire().await.unwrap();
+
+            assert!(!Arc::ptr_eq(client, &new));
+            assert_eq!(new.ready().await, Ok(()));
+        }
+
+        let reply = pool
+            .exec(ECHO_PATH, (ECHO_TRAIT, ECHO_ELEMENT), 42u32)
+            .await
+            .unwrap();
+        assert_eq!(reply.value(), Some(ValueView::UInt32(42)));
+    }
+}
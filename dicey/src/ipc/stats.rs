@@ -0,0 +1,151 @@
+/*
+ * Copyright (c) 2014-2024 Zuru Tech HK Limited, All rights reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::Error;
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ClientStats {
+    pub requests_sent: u64,
+    pub responses_received: u64,
+    pub events_received: u64,
+    pub pending_requests: u64,
+
+    // failed requests by error, only errors that occurred at least once are listed
+    pub errors: Vec<(Error, u64)>,
+}
+
+impl ClientStats {
+    pub fn errors_of(&self, kind: Error) -> u64 {
+        self.errors
+            .iter()
+            .find_map(|&(err, count)| (err == kind).then_some(count))
+            .unwrap_or(0)
+    }
+
+    pub fn total_errors(&self) -> u64 {
+        self.errors.iter().map(|(_, count)| count).sum()
+    }
+}
+
+pub(super) struct StatsCounters {
+    requests_sent: AtomicU64,
+    responses_received: AtomicU64,
+    events_received: AtomicU64,
+    pending_requests: AtomicU64,
+
+    // one counter per entry of Error::ALL
+    errors: Box<[AtomicU64]>,
+}
+
+impl StatsCounters {
+    pub(super) fn new() -> Self {
+        Self {
+            requests_sent: AtomicU64::new(0),
+            responses_received: AtomicU64::new(0),
+            events_received: AtomicU64::new(0),
+            pending_requests: AtomicU64::new(0),
+            errors: Error::ALL.iter().map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    pub(super) fn on_event(&self) {
+        self.events_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn on_request(&self) {
+        self.requests_sent.fetch_add(1, Ordering::Relaxed);
+        self.pending_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn on_reply<T>(&self, reply: &Result<T, Error>) {
+        self.pending_requests.fetch_sub(1, Ordering::Relaxed);
+
+        match reply {
+            Ok(_) => {
+                self.responses_received.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(err) => {
+                if let Some(index) = Error::ALL.iter().position(|e| e == err) {
+                    self.errors[index].fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    // the pending count is left alone, given that it describes requests still in flight
+    pub(super) fn reset(&self) {
+        self.requests_sent.store(0, Ordering::Relaxed);
+        self.responses_received.store(0, Ordering::Relaxed);
+        self.events_received.store(0, Ordering::Relaxed);
+
+        for counter in &self.errors {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub(super) fn snapshot(&self) -> ClientStats {
+        ClientStats {
+            requests_sent: self.requests_sent.load(Ordering::Relaxed),
+            responses_received: self.responses_received.load(Ordering::Relaxed),
+            events_received: self.events_received.load(Ordering::Relaxed),
+            pending_requests: self.pending_requests.load(Ordering::Relaxed),
+            errors: Error::ALL
+                .iter()
+                .zip(&self.errors)
+                .filter_map(|(&err, counter)| {
+                    let count = counter.load(Ordering::Relaxed);
+
+                    (count > 0).then_some((err, count))
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters() {
+        let counters = StatsCounters::new();
+
+        counters.on_request();
+        counters.on_request();
+        counters.on_reply(&Ok(()));
+        counters.on_event();
+
+        let stats = counters.snapshot();
+        assert_eq!(stats.requests_sent, 2);
+        assert_eq!(stats.responses_received, 1);
+        assert_eq!(stats.pending_requests, 1);
+        assert_eq!(stats.events_received, 1);
+
+        counters.on_reply(&Err::<(), _>(Error::TimedOut));
+        counters.reset();
+        counters.on_request();
+        counters.on_reply(&Err::<(), _>(Error::TimedOut));
+
+        let stats = counters.snapshot();
+        assert_eq!(stats.requests_sent, 1);
+        assert_eq!(stats.pending_requests, 0);
+        assert_eq!(stats.errors, vec![(Error::TimedOut, 1)]);
+        assert_eq!(stats.errors_of(Error::TimedOut), 1);
+        assert_eq!(stats.total_errors(), 1);
+    }
+}
@@ -21,13 +21,27 @@
 mod core;
 mod ipc;
 
+#[cfg(feature = "testing")]
+pub mod testing;
+
 pub use self::{
     core::{
-        Bye, ErrorMessage, FromDicey, Hello, Message, MessageBuilder, Op, Packet, Selector,
-        ToDicey, ValueBuilder, ValueView, errors::*,
+        AnyOp, BuildOp, Bye, Byte, ErrorMessage, EventOp, ExecOp, FromDicey, GetOp, Hello, Index,
+        ListIter, MAX_BYTES_LEN, MAX_LIST_LEN, MAX_PACKET_LEN, MAX_PATH_LEN, Message,
+        MessageBuilder, Op, OwnedMessage, OwnedValue, Packet, PacketKind, Path, PathBuf, Primitive,
+        ResponseOp, Selector, SelectorBuf, SetOp, Signature, SignatureMismatch, ToDicey, Tuple2,
+        Type, TypedListBuilder, TypedValueBuilder, ValueBuilder, ValueOp, ValueView, ValuedSetOp,
+        check_abi, errors::*,
+    },
+    ipc::{
+        Address, ChangedElement, Direction, Element, Elements, Inspector, MalformedEntry,
+        MergePolicy, ObjectInfo, Operation, PartialSubscription, Property, SchemaDiff, Signal,
+        Traits, Transport, blocking, supported_transports,
     },
-    ipc::{Address, Element, Elements, ObjectInfo, Operation, Property, Signal, Traits, blocking},
 };
 
 #[cfg(feature = "async")]
-pub use self::ipc::{Client, RequestBuilder, SignalSource};
+pub use self::ipc::{
+    Client, ClientArgs, ClientPool, ClientStats, EventSink, PropertyHandle, RequestBuilder,
+    SignalSource, Subscription, timeout,
+};
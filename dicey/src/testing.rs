@@ -0,0 +1,450 @@
+/*
+ * Copyright (c) 2014-2024 Zuru Tech HK Limited, All rights reserved.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// An in-process libdicey server exposing a couple of canned objects, mirroring the ones the libdicey sample server
+// provides, so that client code can be tested without spawning external processes.
+
+use std::{
+    ffi::{CStr, CString, c_void},
+    mem, process, ptr,
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Sender},
+    },
+    thread::{self, JoinHandle},
+};
+
+use dicey_sys::{
+    dicey_arg, dicey_arg__bindgen_ty_1, dicey_element_new_entry, dicey_element_type,
+    dicey_element_type_DICEY_ELEMENT_TYPE_OPERATION,
+    dicey_element_type_DICEY_ELEMENT_TYPE_PROPERTY, dicey_element_type_DICEY_ELEMENT_TYPE_SIGNAL,
+    dicey_error, dicey_error_DICEY_OK, dicey_error_msg, dicey_op_DICEY_OP_GET,
+    dicey_op_DICEY_OP_SET, dicey_registry_add_object_with_trait_list,
+    dicey_registry_add_trait_with_element_list, dicey_request, dicey_request_acknowledge,
+    dicey_request_fail, dicey_request_get_message, dicey_request_get_op, dicey_request_reply,
+    dicey_request_reply_with_existing, dicey_server, dicey_server_args, dicey_server_delete,
+    dicey_server_get_context, dicey_server_get_registry, dicey_server_new,
+    dicey_server_raise_and_wait, dicey_server_set_context, dicey_server_start,
+    dicey_server_stop_and_wait, dicey_type_DICEY_TYPE_STR,
+};
+
+use crate::{Address, Error, Message, Op, ValueView, check_abi, core::macros::ccall};
+
+pub const ECHO_PATH: &str = "/dicey/test/echo";
+pub const ECHO_TRAIT: &str = "dicey.test.Echo";
+pub const ECHO_ELEMENT: &str = "Echo";
+pub const ECHO_SIGNATURE: &str = "v -> v";
+
+pub const SVAL_PATH: &str = "/sval";
+pub const SVAL_TRAIT: &str = "sval.Sval";
+pub const SVAL_PROP: &str = "Value";
+pub const SVAL_SIGNATURE: &str = "s";
+
+/// Fails with the `i16` error code it's called with, as a server-defined error would.
+pub const FAIL_PATH: &str = "/dicey/test/fail";
+pub const FAIL_TRAIT: &str = "dicey.test.Fail";
+pub const FAIL_ELEMENT: &str = "Fail";
+pub const FAIL_SIGNATURE: &str = "n -> $";
+
+pub const SIGNAL_TRAIT: &str = "dicey.test.Signal";
+pub const SIGNAL_ELEMENT: &str = "Signal";
+pub const SIGNAL_SIGNATURE: &str = "u";
+
+/// The objects implementing [`SIGNAL_TRAIT`], so that tests can subscribe to more than one path.
+pub const SIGNAL_PATHS: &[&str] = &[
+    "/dicey/test/signal/0",
+    "/dicey/test/signal/1",
+    "/dicey/test/signal/2",
+    "/dicey/test/signal/3",
+    "/dicey/test/signal/4",
+    "/dicey/test/signal/5",
+    "/dicey/test/signal/6",
+    "/dicey/test/signal/7",
+];
+
+// (paths, trait, element kind, element name, signature)
+const OBJECTS: &[(&[&str], &str, dicey_element_type, &str, &str)] = &[
+    (
+        &[ECHO_PATH],
+        ECHO_TRAIT,
+        dicey_element_type_DICEY_ELEMENT_TYPE_OPERATION,
+        ECHO_ELEMENT,
+        ECHO_SIGNATURE,
+    ),
+    (
+        &[SVAL_PATH],
+        SVAL_TRAIT,
+        dicey_element_type_DICEY_ELEMENT_TYPE_PROPERTY,
+        SVAL_PROP,
+        SVAL_SIGNATURE,
+    ),
+    (
+        &[FAIL_PATH],
+        FAIL_TRAIT,
+        dicey_element_type_DICEY_ELEMENT_TYPE_OPERATION,
+        FAIL_ELEMENT,
+        FAIL_SIGNATURE,
+    ),
+    (
+        SIGNAL_PATHS,
+        SIGNAL_TRAIT,
+        dicey_element_type_DICEY_ELEMENT_TYPE_SIGNAL,
+        SIGNAL_ELEMENT,
+        SIGNAL_SIGNATURE,
+    ),
+];
+
+/// A libdicey server running on a background thread, listening on a socket of its own.
+///
+/// It serves an echo operation at [`ECHO_PATH`], a settable string property at [`SVAL_PATH`], an always failing
+/// operation at [`FAIL_PATH`] and a signal at each of [`SIGNAL_PATHS`]. The server is stopped and its socket removed on drop.
+pub struct TestServer {
+    ptr: *mut dicey_server,
+    thread: Option<JoinHandle<()>>,
+    pipe: String,
+
+    // must outlive the server, which keeps a pointer to it as its context
+    _state: Box<ServerState>,
+}
+
+impl TestServer {
+    pub fn start() -> Result<Self, Error> {
+        Self::start_at(unique_pipe_name())
+    }
+
+    /// Stops the server and starts a fresh one listening on the same address.
+    ///
+    /// Clients connected to the old server lose their connection, and the canned objects go back to their defaults.
+    pub fn restart(self) -> Result<Self, Error> {
+        let pipe = self.pipe.clone();
+
+        drop(self);
+
+        Self::start_at(pipe)
+    }
+
+    fn start_at(pipe: String) -> Result<Self, Error> {
+        check_abi()?;
+
+        let ptr = unsafe {
+            let mut server = ptr::null_mut();
+
+            ccall!(
+                server_new,
+                &mut server,
+                &dicey_server_args {
+                    on_startup: Some(server_on_startup),
+                    on_request: Some(server_on_request),
+                    ..mem::zeroed()
+                }
+            )?;
+
+            server
+        };
+
+        let (tx, rx) = mpsc::channel();
+
+        // ensure that Drop will run if something goes wrong
+        let mut server = Self {
+            ptr,
+            thread: None,
+            pipe,
+            _state: Box::new(ServerState {
+                sval: Mutex::new(CString::default()),
+                on_startup: Mutex::new(Some(tx.clone())),
+            }),
+        };
+
+        unsafe {
+            dicey_server_set_context(ptr, &*server._state as *const _ as *mut c_void);
+
+            fill_registry(ptr)?;
+        }
+
+        #[cfg(unix)]
+        let _ = std::fs::remove_file(&server.pipe);
+
+        let addr = SendPtr(Address::from(&server.pipe).into_raw());
+        let sptr = SendPtr(ptr);
+
+        server.thread = Some(thread::spawn(move || {
+            let (SendPtr(addr), SendPtr(ptr)) = (addr, sptr);
+
+            // errors after startup are reported by the callback
+            let err = unsafe { dicey_server_start(ptr, addr) };
+            if err != dicey_error_DICEY_OK {
+                let _ = tx.send(Error::from(err));
+            }
+        }));
+
+        match rx.recv() {
+            Ok(Error::OK) => Ok(server),
+            Ok(err) => Err(err),
+            Err(_) => Err(Error::Cancelled),
+        }
+    }
+
+    pub fn address(&self) -> Address {
+        Address::from(&self.pipe)
+    }
+
+    /// Sends `event` to the clients subscribed to it, returning once it has been written out.
+    pub fn raise(&self, event: Message) -> Result<(), Error> {
+        if event.op() != Op::Event {
+            return Err(Error::InvalidData);
+        }
+
+        unsafe { ccall!(server_raise_and_wait, self.ptr, event.into_raw()) }?;
+
+        Ok(())
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some(thread) = self.thread.take() {
+                // fails if the server never started, in which case the thread is already done
+                let _ = dicey_server_stop_and_wait(self.ptr);
+
+                let _ = thread.join();
+            }
+
+            dicey_server_delete(self.ptr);
+        }
+
+        #[cfg(unix)]
+        let _ = std::fs::remove_file(&self.pipe);
+    }
+}
+
+struct ServerState {
+    sval: Mutex<CString>,
+
+    // taken by the first startup notification
+    on_startup: Mutex<Option<Sender<Error>>>,
+}
+
+impl ServerState {
+    unsafe fn on_sval(&self, req: *mut dicey_request) -> dicey_error {
+        unsafe {
+            match dicey_request_get_op(req) {
+                dicey_op_DICEY_OP_GET => {
+                    let sval = self.sval.lock().unwrap();
+
+                    dicey_request_reply(
+                        req,
+                        dicey_arg {
+                            type_: dicey_type_DICEY_TYPE_STR,
+                            __bindgen_anon_1: dicey_arg__bindgen_ty_1 {
+                                str_: sval.as_ptr(),
+                            },
+                        },
+                    )
+                }
+
+                dicey_op_DICEY_OP_SET => {
+                    let msg = &*dicey_request_get_message(req);
+
+                    match ValueView::try_from(msg.value) {
+                        Ok(ValueView::String(s)) => {
+                            // strings coming from dicey values can't contain NULs
+                            *self.sval.lock().unwrap() = CString::new(s).unwrap();
+
+                            dicey_request_acknowledge(req)
+                        }
+                        _ => Error::ValueTypeMismatch.code() as dicey_error,
+                    }
+                }
+
+                _ => Error::NotSupported.code() as dicey_error,
+            }
+        }
+    }
+}
+
+// raw pointers aren't Send, but the server thread only uses them while the TestServer they belong to is alive
+struct SendPtr<T>(T);
+
+unsafe impl<T> Send for SendPtr<T> {}
+
+unsafe fn fill_registry(server: *mut dicey_server) -> Result<(), Error> {
+    let registry = unsafe { dicey_server_get_registry(server) };
+
+    for &(paths, tname, kind, elem, sig) in OBJECTS {
+        let tname = CString::new(tname).unwrap();
+        let elem = CString::new(elem).unwrap();
+        let sig = CString::new(sig).unwrap();
+
+        let entry = dicey_element_new_entry {
+            type_: kind,
+            name: elem.as_ptr(),
+            signature: sig.as_ptr(),
+        };
+
+        let traits = [tname.as_ptr(), ptr::null()];
+
+        unsafe {
+            ccall!(
+                registry_add_trait_with_element_list,
+                registry,
+                tname.as_ptr(),
+                &entry,
+                1
+            )?;
+        }
+
+        for &path in paths {
+            let path = CString::new(path).unwrap();
+
+            unsafe {
+                ccall!(
+                    registry_add_object_with_trait_list,
+                    registry,
+                    path.as_ptr(),
+                    traits.as_ptr()
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn unique_pipe_name() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let name = format!(
+        "dicey-test-{}-{}",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    );
+
+    if cfg!(windows) {
+        format!(r"\\.\pipe\{name}")
+    } else {
+        std::env::temp_dir()
+            .join(format!("{name}.sock"))
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+unsafe extern "C" fn server_on_startup(server: *mut dicey_server, error: dicey_error) {
+    let state = unsafe { &*(dicey_server_get_context(server) as *const ServerState) };
+
+    if let Some(tx) = state.on_startup.lock().unwrap().take() {
+        let _ = tx.send(Error::from(error));
+    }
+}
+
+unsafe extern "C" fn server_on_request(server: *mut dicey_server, req: *mut dicey_request) {
+    unsafe {
+        let state = &*(dicey_server_get_context(server) as *const ServerState);
+        let msg = &*dicey_request_get_message(req);
+
+        // the server only lets requests for registered elements through, so the path is enough to tell them apart
+        let err = match CStr::from_ptr(msg.path).to_bytes() {
+            path if path == ECHO_PATH.as_bytes() => {
+                dicey_request_reply_with_existing(req, &msg.value)
+            }
+            path if path == SVAL_PATH.as_bytes() => state.on_sval(req),
+            path if path == FAIL_PATH.as_bytes() => match ValueView::try_from(msg.value) {
+                Ok(ValueView::Int16(code)) => code as dicey_error,
+                _ => Error::ValueTypeMismatch.code() as dicey_error,
+            },
+            _ => Error::PathNotFound.code() as dicey_error,
+        };
+
+        if err != dicey_error_DICEY_OK {
+            let _ = dicey_request_fail(req, err as u16, dicey_error_msg(err));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::blocking::{Client, ClientArgs, Direction};
+
+    #[test]
+    fn test_canned_objects() {
+        let server = TestServer::start().unwrap();
+
+        let client = Client::connect(ClientArgs::new(server.address())).unwrap();
+
+        client.wait_ready().unwrap();
+
+        let reply = client
+            .exec(ECHO_PATH, (ECHO_TRAIT, ECHO_ELEMENT), 42u32)
+            .unwrap();
+        assert_eq!(reply.value(), Some(ValueView::UInt32(42)));
+
+        client
+            .set(SVAL_PATH, (SVAL_TRAIT, SVAL_PROP), "hello")
+            .unwrap();
+
+        let reply = client.get(SVAL_PATH, (SVAL_TRAIT, SVAL_PROP)).unwrap();
+        assert_eq!(reply.value(), Some(ValueView::String("hello")));
+
+        let reply = client
+            .exec(FAIL_PATH, (FAIL_TRAIT, FAIL_ELEMENT), 1234i16)
+            .unwrap();
+        assert!(matches!(reply.value(), Some(ValueView::Error(e)) if e.code == 1234));
+
+        // codes of the server's own have no library error to map to
+        assert_eq!(
+            client.call::<()>(FAIL_PATH, (FAIL_TRAIT, FAIL_ELEMENT), 1234i16),
+            Err(Error::InvalidData)
+        );
+    }
+
+    #[test]
+    fn test_inspect_packets() {
+        let server = TestServer::start().unwrap();
+
+        let seen = std::sync::Arc::new(Mutex::new(Vec::new()));
+
+        let client = Client::connect(ClientArgs::new(server.address()).inspect({
+            let seen = seen.clone();
+
+            move |direction, bytes: &[u8]| seen.lock().unwrap().push((direction, bytes.to_vec()))
+        }))
+        .unwrap();
+
+        client.wait_ready().unwrap();
+
+        client
+            .exec(ECHO_PATH, (ECHO_TRAIT, ECHO_ELEMENT), 42u32)
+            .unwrap();
+
+        let seen = seen.lock().unwrap();
+        let directions = seen.iter().map(|(d, _)| *d).collect::<Vec<_>>();
+
+        assert_eq!(directions, [Direction::Outbound, Direction::Inbound]);
+
+        for (_, bytes) in seen.iter() {
+            let packet = crate::Packet::load(bytes).unwrap();
+
+            assert_eq!(
+                packet.as_message().unwrap().value(),
+                Some(ValueView::UInt32(42))
+            );
+        }
+    }
+}